@@ -2,33 +2,98 @@ use amuse::midi::{ChannelMessage, Controller, Message};
 use muse::{
     instrument::{serialization, VirtualInstrument},
     node::LoadedInstrument,
-    Note,
+    soundfont::SoundFont,
+    tuning::{self, KeyboardMapping, ScalaScale, ScalaTuning},
+    wav::{Channels, WavFormat},
 };
 
-use std::{convert::TryInto, error::Error};
+use std::{convert::TryInto, error::Error, time::Duration};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let instrument: LoadedInstrument<()> =
-        ron::from_str::<serialization::Instrument>(include_str!("support/basic_synth.ron"))?
-            .try_into()?;
+    // Arguments ending in .sf2 are played as soundfonts, .scl and .kbm files
+    // retune the keyboard, --mono plays one note at a time, and any other
+    // argument records the performance to a WAV file while it plays.
+    let (flags, paths): (Vec<_>, Vec<_>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+    let (soundfont_paths, paths): (Vec<_>, Vec<_>) =
+        paths.into_iter().partition(|arg| arg.ends_with(".sf2"));
+    let (scale_paths, paths): (Vec<_>, Vec<_>) =
+        paths.into_iter().partition(|arg| arg.ends_with(".scl"));
+    let (mapping_paths, recording_paths): (Vec<_>, Vec<_>) =
+        paths.into_iter().partition(|arg| arg.ends_with(".kbm"));
+    let soundfont = soundfont_paths.first().map(SoundFont::open).transpose()?;
+
+    let instrument: LoadedInstrument<()> = match &soundfont {
+        Some(soundfont) => soundfont.preset(0, 0)?,
+        None => {
+            ron::from_str::<serialization::Instrument>(include_str!("support/basic_synth.ron"))?
+                .try_into()?
+        }
+    };
     let mut instrument = VirtualInstrument::new_with_default_output(instrument)?;
+    instrument.set_mono(flags.iter().any(|flag| flag == "--mono"));
+    if let Some(scale) = scale_paths.first() {
+        let scale = ScalaScale::open(scale)?;
+        let mapping = match mapping_paths.first() {
+            Some(mapping) => KeyboardMapping::open(mapping)?,
+            None => KeyboardMapping::linear(&scale),
+        };
+        instrument.set_tuning(ScalaTuning::new(scale, mapping));
+    }
+
+    let _recording = recording_paths
+        .first()
+        .map(|path| {
+            instrument
+                .device()
+                .record(path, Channels::Stereo, WavFormat::Float32)
+        })
+        .transpose()?;
 
     let messages = amuse::midi::open_named_input("midisynth");
 
     while let Ok(message) = messages.recv() {
         if let Message::Channel { channel, message } = &message {
             if channel == &0 {
+                if let Some((control, value)) = message.control_value() {
+                    instrument.set_control(&control, value);
+                }
+
                 match message {
-                    ChannelMessage::NoteOff { key, .. } => instrument.stop_note(*key),
-                    ChannelMessage::NoteOn { key, velocity } => instrument
-                        .play_note(Note::new(*key as f32, *velocity))
-                        .unwrap(),
-                    ChannelMessage::ControlChange { controller, value } => match controller {
-                        Controller::Damper => instrument.set_sustain(value > &0x40),
-                        _ => println!(
-                            "Unrecognized controller changed {:?}, value {}",
-                            controller, value
-                        ),
+                    ChannelMessage::NoteOff { key, .. } => instrument.stop_key(*key),
+                    ChannelMessage::NoteOn { key, velocity } => {
+                        if let Err(err) = instrument.play_key(*key, *velocity) {
+                            // Keys outside the keyboard mapping are silent
+                            if !matches!(err.downcast_ref(), Some(tuning::Error::UnmappedKey(_))) {
+                                return Err(err.into());
+                            }
+                        }
+                    }
+                    ChannelMessage::ControlChange {
+                        controller: Controller::Damper,
+                        value,
+                    } => instrument.set_sustain(value > &0x40),
+                    ChannelMessage::ControlChange {
+                        controller: Controller::Portamento,
+                        value,
+                    } => instrument.set_portamento(value >= &0x40),
+                    ChannelMessage::ControlChange {
+                        controller: Controller::PortamentoTime,
+                        value,
+                    } => instrument.set_glide_time(Duration::from_millis(*value as u64 * 10)),
+                    ChannelMessage::PitchBend { amount } => {
+                        instrument.set_pitch_bend(*amount as f32 / 8192.)
+                    }
+                    // Other controllers only update the control bus
+                    ChannelMessage::ControlChange { .. }
+                    | ChannelMessage::ChannelPressure { .. } => {}
+                    ChannelMessage::ProgramChange { program } => match &soundfont {
+                        Some(soundfont) => match soundfont.preset(0, *program as u16) {
+                            Ok(preset) => instrument.set_tone_generator(preset),
+                            Err(err) => println!("Error changing program: {}", err),
+                        },
+                        None => println!("Program changes require a soundfont"),
                     },
                     unhandled => println!("Unhandled channel message: {:?}", unhandled),
                 }
@@ -28,18 +28,23 @@ fn main() -> Result<(), Box<dyn Error>> {
     let instrument: LoadedInstrument<()> =
         ron::from_str::<serialization::Instrument>(include_str!("support/basic_synth.ron"))?
             .try_into()?;
+    let (c4, d4, e4) = (
+        Note::named("C4", 80)?,
+        Note::named("D4", 80)?,
+        Note::named("E4", 80)?,
+    );
     let voice = VoiceBuilder::new(instrument)
         .poly(|p| {
-            p.part(|p| p.play(Note::new(64., 80)).hold_for(NoteDuration::whole()))
-                .part(|p| p.play(Note::new(60., 80)).hold_for(NoteDuration::whole()))
+            p.part(|p| p.play(e4).hold_for(NoteDuration::whole()))
+                .part(|p| p.play(c4).hold_for(NoteDuration::whole()))
         })
-        .play(Note::new(64., 80))
+        .play(e4)
         .hold_for(NoteDuration::whole())
         .release()
-        .play(Note::new(62., 80))
+        .play(d4)
         .hold_for(NoteDuration::whole().dotted())
         .release()
-        .play(Note::new(60., 80))
+        .play(c4)
         .hold_for(NoteDuration::whole())
         .build();
     let choir = Choir::new(vec![voice]);
@@ -76,7 +76,7 @@ fn connect_to_available_inputs(
     };
 
     for port_name in port_names_to_try {
-        connect_to_input(app_name, port_name, &sender, open_ports)?;
+        connect_to_input(app_name, port_name, sender, open_ports)?;
     }
 
     Ok(())
@@ -93,11 +93,11 @@ fn connect_to_input(
     if let Some(port) = input
         .ports()
         .into_iter()
-        .find(|p| input.port_name(&p).unwrap_or_default() == port_name)
+        .find(|p| input.port_name(p).unwrap_or_default() == port_name)
     {
         let connection = input.connect(
             &port,
-            &app_name,
+            app_name,
             move |_, message, sender| handle_message(message, sender),
             sender.clone(),
         )?;
@@ -88,6 +88,26 @@ pub enum ChannelMessage {
     PitchBend { amount: i16 },
 }
 
+impl ChannelMessage {
+    /// The name and value of the control this message updates, using the
+    /// names described by `muse::instrument::ControlBus`. Pitch bend ranges
+    /// from -1 to 1, and everything else ranges from 0 to 1.
+    pub fn control_value(&self) -> Option<(String, f32)> {
+        match self {
+            ChannelMessage::ControlChange { controller, value } => {
+                Some((format!("cc{}", u8::from(controller)), *value as f32 / 127.))
+            }
+            ChannelMessage::ChannelPressure { pressure } => {
+                Some(("channel_pressure".to_owned(), *pressure as f32 / 127.))
+            }
+            ChannelMessage::PitchBend { amount } => {
+                Some(("pitch_bend".to_owned(), (*amount as f32 / 8192.).max(-1.)))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Controller {
     Undefined(u8),
@@ -134,3 +154,50 @@ impl From<u8> for Controller {
         }
     }
 }
+
+impl From<&Controller> for u8 {
+    fn from(controller: &Controller) -> Self {
+        match controller {
+            Controller::ModulationWheel => 1,
+            Controller::BreathController => 2,
+            Controller::FootController => 4,
+            Controller::PortamentoTime => 5,
+            Controller::DataEntrySlider => 6,
+            Controller::MainVolume => 7,
+            Controller::Balance => 8,
+            Controller::Pan => 10,
+            Controller::ExpressionController => 11,
+            Controller::GeneralPurpose1 => 16,
+            Controller::GeneralPurpose2 => 17,
+            Controller::GeneralPurpose3 => 18,
+            Controller::GeneralPurpose4 => 19,
+            Controller::Damper => 64,
+            Controller::Portamento => 65,
+            Controller::Sostenuto => 66,
+            Controller::SoftPedal => 67,
+            Controller::Undefined(number) => *number,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_values() {
+        let message = |bytes: &[u8]| match Message::from(bytes) {
+            Message::Channel { message, .. } => message.control_value(),
+            _ => unreachable!(),
+        };
+        assert_eq!(message(&[0xB0, 1, 127]), Some(("cc1".to_owned(), 1.)));
+        assert_eq!(message(&[0xB0, 74, 0]), Some(("cc74".to_owned(), 0.)));
+        assert_eq!(
+            message(&[0xD0, 127]),
+            Some(("channel_pressure".to_owned(), 1.))
+        );
+        assert_eq!(message(&[0xE0, 0, 0]), Some(("pitch_bend".to_owned(), -1.)));
+        assert_eq!(message(&[0xE0, 0, 64]), Some(("pitch_bend".to_owned(), 0.)));
+        assert_eq!(message(&[0x90, 60, 100]), None);
+    }
+}
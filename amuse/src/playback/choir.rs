@@ -1,7 +1,7 @@
 use muse::{
+    manager::Device,
     node::Instantiatable,
-    prelude::{ToneGenerator, VirtualInstrument},
-    Note,
+    prelude::{NoteId, ToneGenerator, VirtualInstrument},
 };
 
 use crate::playback::voice::Voice;
@@ -29,6 +29,14 @@ where
     T: ToneGenerator + Clone + Instantiatable + 'static,
 {
     pub fn play(&self) -> anyhow::Result<()> {
+        self.play_on(Device::default_output)
+    }
+
+    /// Plays each voice on a device created by `new_device`, such as a null or file device.
+    pub fn play_on<F>(&self, mut new_device: F) -> anyhow::Result<()>
+    where
+        F: FnMut() -> anyhow::Result<Device>,
+    {
         let mut current_beat = NoteDuration::default();
         let beats_per_minute = 60f32;
         let mut voices = self
@@ -36,7 +44,7 @@ where
             .iter()
             .filter_map(|voice| {
                 let instrument =
-                    VirtualInstrument::new_with_default_output(voice.instrument.clone()).ok()?;
+                    VirtualInstrument::new(new_device().ok()?, voice.instrument.clone());
                 Some(ChoirVoice {
                     state: SequenceState::default(),
                     instrument,
@@ -122,13 +130,12 @@ where
             state.current_step = Some(next_step_index);
             match &step.command {
                 VoiceCommand::Play(note) => {
-                    instrument.play_note(*note).unwrap();
-                    state.playing_note = Some(*note);
+                    state.playing_note = Some(instrument.play_note(*note).unwrap());
                 }
 
                 VoiceCommand::Release => {
-                    if let Some(note) = state.playing_note {
-                        instrument.stop_note(note.step() as u8);
+                    if let Some(id) = state.playing_note.take() {
+                        instrument.stop_note_by_id(id);
                     }
                 }
                 VoiceCommand::Poly(parts) => {
@@ -146,7 +153,7 @@ where
 #[derive(Default)]
 pub struct SequenceState {
     current_step: Option<usize>,
-    playing_note: Option<Note>,
+    playing_note: Option<NoteId>,
     poly_state: Option<PolyState>,
 }
 
@@ -2,9 +2,11 @@ use crate::instrument::ControlHandle;
 
 mod config;
 mod curve;
+mod scaling;
 pub use config::{CurveBuilder, EnvelopeBuilder, EnvelopeConfiguration, EnvelopeCurve, Point};
 use curve::EnvelopeCurveInstance;
 pub use curve::{EnvelopeCurveError, FlattenedCurve};
+pub use scaling::{EnvelopeScaling, StageScaling};
 
 use crate::sampler::FrameInfo;
 
@@ -32,8 +34,6 @@ pub struct Envelope {
     is_playing: ControlHandle,
     last_value: Option<f32>,
 
-    last_playing_check: usize,
-
     attack: EnvelopeCurveInstance,
     hold: EnvelopeCurveInstance,
     decay: EnvelopeCurveInstance,
@@ -1,8 +1,8 @@
 use super::{
     curve::{EnvelopeCurveError, EnvelopeSegment, FlattenedCurve},
-    Envelope, EnvelopeStage,
+    Envelope, EnvelopeScaling, EnvelopeStage, StageScaling,
 };
-use crate::{instrument::ControlHandles, parameter::Parameter};
+use crate::{instrument::ControlHandles, note::Note, parameter::Parameter};
 use kurbo::BezPath;
 use std::{convert::TryFrom, time::Duration};
 
@@ -13,6 +13,7 @@ pub struct EnvelopeBuilder {
     pub decay: Option<EnvelopeCurve>,
     pub sustain: Option<EnvelopeCurve>,
     pub release: Option<EnvelopeCurve>,
+    pub scaling: EnvelopeScaling,
 }
 
 impl EnvelopeBuilder {
@@ -41,6 +42,11 @@ impl EnvelopeBuilder {
         self
     }
 
+    pub fn scaling(mut self, scaling: EnvelopeScaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
     fn flatten_timed_curve(
         curve: Option<EnvelopeCurve>,
         start_value: f32,
@@ -112,6 +118,7 @@ impl EnvelopeBuilder {
             decay,
             sustain,
             release,
+            scaling: self.scaling,
         })
     }
 }
@@ -123,24 +130,25 @@ pub struct EnvelopeConfiguration {
     pub decay: FlattenedCurve,
     pub sustain: FlattenedCurve,
     pub release: FlattenedCurve,
+    pub scaling: EnvelopeScaling,
 }
 
 impl EnvelopeConfiguration {
-    pub fn as_parameter(&self, controls: &ControlHandles) -> Parameter {
+    /// Creates an envelope for `note`, scaled by its velocity and key.
+    pub fn as_parameter(&self, note: &Note, controls: &ControlHandles) -> Parameter {
         let is_playing = controls.new_handle();
         controls.push(is_playing.clone());
+        let scaled = |stage, curve| self.scaling.apply(stage, curve, note).instantiate();
 
         let envelope = Envelope {
             state: EnvelopeStage::Attack,
             last_value: None,
 
-            last_playing_check: 0,
-
-            attack: self.attack.instantiate(),
-            hold: self.hold.instantiate(),
-            decay: self.decay.instantiate(),
-            sustain: self.sustain.instantiate(),
-            release: self.release.instantiate(),
+            attack: scaled(&self.scaling.attack, &self.attack),
+            hold: scaled(&self.scaling.hold, &self.hold),
+            decay: scaled(&self.scaling.decay, &self.decay),
+            sustain: scaled(&StageScaling::default(), &self.sustain),
+            release: scaled(&self.scaling.release, &self.release),
 
             is_playing,
         };
@@ -175,7 +183,7 @@ impl EnvelopeCurve {
 }
 
 #[cfg(feature = "serialization")]
-use crate::instrument::serialization::{EnvelopeCurve as EnvelopeCurveSpec, Error};
+use crate::instrument::serialization::{CurveSegment, EnvelopeCurve as EnvelopeCurveSpec, Error};
 
 #[cfg(feature = "serialization")]
 impl EnvelopeCurve {
@@ -185,6 +193,41 @@ impl EnvelopeCurve {
                 Some(EnvelopeCurve::Timed(Duration::from_millis(*millis as u64)))
             }
             Some(EnvelopeCurveSpec::Sustain(value)) => Some(EnvelopeCurve::Sustain(*value)),
+            Some(EnvelopeCurveSpec::Points(points)) => {
+                let (&start, rest) = points
+                    .split_first()
+                    .filter(|(_, rest)| !rest.is_empty())
+                    .ok_or(EnvelopeCurveError::TooFewPoints)?;
+                let builder = rest
+                    .iter()
+                    .fold(start_curve(start)?, |builder, &(millis, value)| {
+                        builder.line_to(millis / 1000., value)
+                    });
+                Some(builder.build()?)
+            }
+            Some(EnvelopeCurveSpec::Path { start, segments }) => {
+                if segments.is_empty() {
+                    return Err(EnvelopeCurveError::TooFewPoints.into());
+                }
+                let builder = segments
+                    .iter()
+                    .fold(start_curve(*start)?, |builder, segment| match *segment {
+                        CurveSegment::Line((millis, value)) => {
+                            builder.line_to(millis / 1000., value)
+                        }
+                        CurveSegment::Cubic {
+                            to: (millis, value),
+                            start_control,
+                            end_control,
+                        } => builder.curve_to(
+                            millis / 1000.,
+                            value,
+                            control_point(start_control),
+                            control_point(end_control),
+                        ),
+                    });
+                Some(builder.build()?)
+            }
             None => None,
         };
 
@@ -192,6 +235,16 @@ impl EnvelopeCurve {
     }
 }
 
+#[cfg(feature = "serialization")]
+fn control_point((millis, value): (f32, f32)) -> Point {
+    Point::new(millis as f64 / 1000., value as f64)
+}
+
+#[cfg(feature = "serialization")]
+fn start_curve((millis, value): (f32, f32)) -> Result<CurveBuilder, EnvelopeCurveError> {
+    CurveBuilder::default().move_to(millis / 1000., value)
+}
+
 #[derive(Default)]
 pub struct CurveBuilder {
     path: BezPath,
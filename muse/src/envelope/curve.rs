@@ -22,7 +22,27 @@ impl FlattenedCurve {
     }
 
     pub fn start_value(&self) -> Option<f32> {
-        self.segments.get(0).map(|s| s.start_value)
+        self.segments.first().map(|s| s.start_value)
+    }
+
+    /// Multiplies the duration and values of every segment.
+    pub fn scaled(&self, duration: f32, level: f32) -> Self {
+        if duration == 1. && level == 1. {
+            return self.clone();
+        }
+
+        Self {
+            segments: Arc::new(
+                self.segments
+                    .iter()
+                    .map(|segment| EnvelopeSegment {
+                        duration: segment.duration * duration,
+                        start_value: segment.start_value * level,
+                        end_value: segment.end_value * level,
+                    })
+                    .collect(),
+            ),
+        }
     }
 
     pub fn sustain(value: f32) -> Self {
@@ -51,6 +71,8 @@ pub enum EnvelopeCurveError {
     TooComplex,
     #[error("attempting to use the wrong type of curve")]
     InvalidCurveType,
+    #[error("curve must have at least two points")]
+    TooFewPoints,
 }
 
 impl TryFrom<BezPath> for FlattenedCurve {
@@ -0,0 +1,176 @@
+use super::curve::FlattenedCurve;
+use crate::note::Note;
+
+/// Adjusts an envelope for each note based on its velocity and key. The
+/// sustain stage lasts until the note is released, so only its level is
+/// scaled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize),
+    serde(default)
+)]
+pub struct EnvelopeScaling {
+    pub attack: StageScaling,
+    pub hold: StageScaling,
+    pub decay: StageScaling,
+    pub release: StageScaling,
+    /// Scales the levels of every stage rather than a stage's duration.
+    pub level: StageScaling,
+    /// The step that key scaling is relative to. Defaults to C4.
+    pub key_center: f32,
+}
+
+impl Default for EnvelopeScaling {
+    fn default() -> Self {
+        Self {
+            attack: StageScaling::default(),
+            hold: StageScaling::default(),
+            decay: StageScaling::default(),
+            release: StageScaling::default(),
+            level: StageScaling::default(),
+            key_center: 60.,
+        }
+    }
+}
+
+/// How much a note's velocity and key scale a value.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize),
+    serde(default)
+)]
+pub struct StageScaling {
+    /// At 1, the value is multiplied by the note's velocity percent. At 0,
+    /// velocity is ignored. Negative values make quieter notes larger, such
+    /// as -1 doubling the attack of the softest notes.
+    pub velocity: f32,
+    /// The fraction the value grows for each octave above the key center,
+    /// such as -0.25 shortening each octave by a quarter. Lower keys are
+    /// scaled by the inverse.
+    pub key: f32,
+}
+
+impl StageScaling {
+    pub fn velocity(mut self, velocity: f32) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn key(mut self, key: f32) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn factor(&self, note: &Note, key_center: f32) -> f32 {
+        let velocity = 1. + self.velocity * (note.velocity_percent() - 1.);
+        let octaves = (note.step() - key_center) / 12.;
+        velocity.max(0.) * (1. + self.key).max(0.).powf(octaves)
+    }
+}
+
+impl EnvelopeScaling {
+    pub fn attack(mut self, attack: StageScaling) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    pub fn hold(mut self, hold: StageScaling) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    pub fn decay(mut self, decay: StageScaling) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    pub fn release(mut self, release: StageScaling) -> Self {
+        self.release = release;
+        self
+    }
+
+    pub fn level(mut self, level: StageScaling) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn key_center(mut self, key_center: f32) -> Self {
+        self.key_center = key_center;
+        self
+    }
+
+    /// Scales `curve` for `note` using `stage` for its durations.
+    pub(crate) fn apply(
+        &self,
+        stage: &StageScaling,
+        curve: &FlattenedCurve,
+        note: &Note,
+    ) -> FlattenedCurve {
+        curve.scaled(
+            stage.factor(note, self.key_center),
+            self.level.factor(note, self.key_center),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        envelope::{EnvelopeBuilder, EnvelopeCurve},
+        instrument::ControlHandles,
+        sampler::FrameInfo,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn velocity_and_key_scale_envelopes() {
+        let scaling = StageScaling::default().velocity(1.).key(-0.5);
+        assert_eq!(scaling.factor(&Note::new(60., 127), 60.), 1.);
+        assert!((scaling.factor(&Note::new(72., 127), 60.) - 0.5).abs() < 0.001);
+        assert!((scaling.factor(&Note::new(48., 127), 60.) - 2.).abs() < 0.001);
+        assert!((scaling.factor(&Note::new(60., 0), 60.)).abs() < 0.001);
+
+        let envelope = EnvelopeBuilder::default()
+            .attack(EnvelopeCurve::Timed(Duration::from_millis(100)))
+            .sustain(EnvelopeCurve::Sustain(1.))
+            .scaling(
+                EnvelopeScaling::default()
+                    .attack(StageScaling::default().velocity(-1.).key(-0.5))
+                    .level(StageScaling::default().velocity(1.)),
+            )
+            .build()
+            .unwrap();
+        // Samples the envelope at 1kHz until it reaches its sustain level
+        let attack = |note: Note| {
+            let mut parameter = envelope.as_parameter(&note, &ControlHandles::new());
+            let values = (1..1000)
+                .map(|clock| {
+                    parameter
+                        .next(&FrameInfo {
+                            clock,
+                            sample_rate: 1_000,
+                            note,
+                        })
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let sustain = *values.last().unwrap();
+            let frames = values.iter().position(|&value| value >= sustain).unwrap();
+            (frames, sustain)
+        };
+
+        let (frames, level) = attack(Note::new(60., 127));
+        assert!((99..=101).contains(&frames));
+        assert_eq!(level, 1.);
+        // An octave higher attacks twice as fast
+        let (frames, _) = attack(Note::new(72., 127));
+        assert!((49..=51).contains(&frames));
+        // Softer notes attack slower and quieter
+        let (frames, level) = attack(Note::new(60., 64));
+        assert!((148..=151).contains(&frames));
+        assert!((level - 64. / 127.).abs() < 0.001);
+    }
+}
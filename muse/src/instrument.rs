@@ -3,7 +3,8 @@ use crate::{
     manager::{Device, PlayingHandle},
     node::{Instantiatable, LoadedInstrument},
     note::Note,
-    sampler::PreparedSampler,
+    sampler::{PreparableSampler, PreparedSampler},
+    tuning::{self, EqualDivision, Tuning},
 };
 use crossbeam::atomic::AtomicCell;
 use std::{
@@ -11,8 +12,13 @@ use std::{
     time::Duration,
 };
 
+mod control_bus;
 #[cfg(feature = "serialization")]
 pub mod serialization;
+mod voice;
+pub use control_bus::*;
+pub use voice::VoiceStealing;
+use voice::{Voice, VoiceState};
 
 pub struct GeneratedTone<T> {
     pub source: T,
@@ -22,20 +28,34 @@ pub struct GeneratedTone<T> {
 pub type ControlHandle = Arc<AtomicCell<PlayingState>>;
 
 #[derive(Debug, Default)]
-pub struct ControlHandles(Arc<RwLock<Vec<ControlHandle>>>);
+pub struct ControlHandles {
+    handles: Arc<RwLock<Vec<ControlHandle>>>,
+    bus: ControlBus,
+}
 
 impl ControlHandles {
     pub fn new() -> Self {
-        Self(Arc::new(RwLock::new(Vec::new())))
+        Self::default()
+    }
+
+    pub fn with_bus(bus: ControlBus) -> Self {
+        Self {
+            handles: Default::default(),
+            bus,
+        }
+    }
+
+    pub fn bus(&self) -> &ControlBus {
+        &self.bus
     }
 
     pub fn push(&self, value: ControlHandle) {
-        let mut vec = self.0.write().unwrap();
+        let mut vec = self.handles.write().unwrap();
         vec.push(value);
     }
 
     pub fn is_playing(&self) -> bool {
-        let vec = self.0.read().unwrap();
+        let vec = self.handles.read().unwrap();
         for control in vec.iter() {
             if let PlayingState::Playing = control.load() {
                 return true;
@@ -46,14 +66,14 @@ impl ControlHandles {
     }
 
     fn stop(&self) {
-        let vec = self.0.read().unwrap();
+        let vec = self.handles.read().unwrap();
         for control in vec.iter() {
             control.store(PlayingState::Stopping);
         }
     }
 
     fn stopped(&self) -> bool {
-        let control_handles = self.0.read().unwrap();
+        let control_handles = self.handles.read().unwrap();
         control_handles
             .iter()
             .map(|control| control.load())
@@ -61,7 +81,7 @@ impl ControlHandles {
     }
 
     fn sustain(&self) {
-        let vec = self.0.read().unwrap();
+        let vec = self.handles.read().unwrap();
         for control in vec.iter() {
             control.store(PlayingState::Sustaining);
         }
@@ -69,7 +89,7 @@ impl ControlHandles {
 
     pub fn new_handle(&self) -> ControlHandle {
         let handle = Arc::new(AtomicCell::new(PlayingState::Playing));
-        let mut vec = self.0.write().unwrap();
+        let mut vec = self.handles.write().unwrap();
         vec.push(handle.clone());
         handle
     }
@@ -83,9 +103,15 @@ pub struct InstrumentController<T> {
 
 impl<T> Default for InstrumentController<T> {
     fn default() -> Self {
+        Self::new(ControlBus::default())
+    }
+}
+
+impl<T> InstrumentController<T> {
+    pub fn new(bus: ControlBus) -> Self {
         Self {
-            control_handles: ControlHandles::new(),
-            _tone_generator: std::marker::PhantomData::default(),
+            control_handles: ControlHandles::with_bus(bus),
+            _tone_generator: std::marker::PhantomData,
         }
     }
 }
@@ -114,10 +140,23 @@ pub trait ToneGenerator: Sized {
     ) -> Result<PreparedSampler, anyhow::Error>;
 }
 
+/// How long stolen voices take to fade out.
+const STEAL_FADE: Duration = Duration::from_millis(10);
+
+/// Identifies a note played by a [`VirtualInstrument`]. Later notes have
+/// greater ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NoteId(u64);
+
 pub struct PlayingNote<T> {
     note: Note,
+    id: NoteId,
+    /// The key that played this note, if it was played with
+    /// [`VirtualInstrument::play_key`].
+    key: Option<u8>,
     handle: Option<PlayingHandle>,
     controller: InstrumentController<T>,
+    voice: Arc<VoiceState>,
 }
 
 impl<T> PlayingNote<T> {
@@ -125,6 +164,10 @@ impl<T> PlayingNote<T> {
         self.controller.control_handles.is_playing()
     }
 
+    fn is_finished(&self) -> bool {
+        self.voice.finished() || self.controller.control_handles.stopped()
+    }
+
     fn stop(&self) {
         self.controller.control_handles.stop()
     }
@@ -140,11 +183,11 @@ impl<T> Drop for PlayingNote<T> {
 
         let handle = std::mem::take(&mut self.handle);
         let control_handles = std::mem::take(&mut self.controller.control_handles);
+        let voice = self.voice.clone();
 
         std::thread::spawn(move || loop {
             {
-                if control_handles.stopped() {
-                    println!("Sound stopping");
+                if voice.finished() || control_handles.stopped() {
                     drop(handle);
                     return;
                 }
@@ -161,11 +204,31 @@ pub enum Loudness {
     Pianissimo,
 }
 
+struct HeldNote {
+    id: NoteId,
+    key: Option<u8>,
+    note: Note,
+}
+
 pub struct VirtualInstrument<T> {
     playing_notes: Vec<PlayingNote<T>>,
+    /// Notes whose keys have been released but may still be sounding.
+    releasing_notes: Vec<PlayingNote<T>>,
+    last_note_id: u64,
+    max_polyphony: Option<usize>,
+    voice_stealing: VoiceStealing,
     device: Device,
     sustain: bool,
     tone_generator: T,
+    tuning: Box<dyn Tuning>,
+    controls: ControlBus,
+    pitch_bend: f32,
+    pitch_bend_range: f32,
+    mono: bool,
+    /// The notes held in mono mode, with the sounding note last.
+    held_notes: Vec<HeldNote>,
+    portamento: bool,
+    glide_time: Duration,
 }
 
 impl<T> VirtualInstrument<T>
@@ -177,7 +240,19 @@ where
             device,
             tone_generator,
             playing_notes: Vec::new(),
+            releasing_notes: Vec::new(),
+            last_note_id: 0,
+            max_polyphony: None,
+            voice_stealing: VoiceStealing::default(),
             sustain: false,
+            tuning: Box::new(EqualDivision::default()),
+            controls: ControlBus::new(),
+            pitch_bend: 0.,
+            pitch_bend_range: 2.,
+            mono: false,
+            held_notes: Vec::new(),
+            portamento: false,
+            glide_time: Duration::from_millis(100),
         }
     }
 
@@ -186,37 +261,294 @@ where
         Ok(Self::new(device, tone_generator))
     }
 
-    pub fn play_note(&mut self, note: Note) -> Result<(), anyhow::Error> {
-        // We need to re-tone the note, so we'll get rid of the existing notes
+    pub fn new_offline(sample_rate: u32, tone_generator: T) -> Self {
+        Self::new(Device::offline(sample_rate), tone_generator)
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Changes the sound of future notes. Notes that are already playing
+    /// keep their original tone.
+    pub fn set_tone_generator(&mut self, tone_generator: T) {
+        self.tone_generator = tone_generator;
+    }
+
+    /// Changes how [`Self::play_key`] maps keys to frequencies. Defaults to
+    /// twelve-tone equal temperament with A4 = 440Hz.
+    pub fn set_tuning<U: Tuning + 'static>(&mut self, tuning: U) {
+        self.tuning = Box::new(tuning);
+    }
+
+    pub fn tuning(&self) -> &dyn Tuning {
+        self.tuning.as_ref()
+    }
+
+    pub fn controls(&self) -> &ControlBus {
+        &self.controls
+    }
+
+    /// Updates a control for playing and future notes.
+    pub fn set_control(&self, name: &str, value: f32) {
+        self.controls.set(name, value);
+    }
+
+    /// Limits how many notes can sound at once, including notes that are
+    /// still releasing. Unlimited by default.
+    pub fn set_max_polyphony(&mut self, max_polyphony: Option<usize>) {
+        self.max_polyphony = max_polyphony;
+    }
+
+    pub fn set_voice_stealing(&mut self, voice_stealing: VoiceStealing) {
+        self.voice_stealing = voice_stealing;
+    }
+
+    /// The number of notes sounding, excluding stolen notes that are fading
+    /// out.
+    pub fn voice_count(&self) -> usize {
+        self.voices().count()
+    }
+
+    fn voices(&self) -> impl Iterator<Item = &PlayingNote<T>> {
         self.playing_notes
-            .retain(|n| n.note.step() as u8 != note.step() as u8);
+            .iter()
+            .chain(self.releasing_notes.iter())
+            .filter(|note| !note.voice.is_fading() && !note.is_finished())
+    }
+
+    /// Fades out a voice chosen by the voice stealing strategy.
+    fn steal_voice(&mut self, note: Note) {
+        let oldest = |notes: &mut dyn Iterator<Item = &PlayingNote<T>>| {
+            notes.map(|playing| playing.id).min()
+        };
+        let victim = match self.voice_stealing {
+            VoiceStealing::Oldest => oldest(&mut self.voices()),
+            VoiceStealing::Quietest => self
+                .voices()
+                .min_by(|a, b| a.voice.level().total_cmp(&b.voice.level()))
+                .map(|playing| playing.id),
+            VoiceStealing::SameNote => oldest(
+                &mut self
+                    .voices()
+                    .filter(|playing| (playing.note.step() - note.step()).abs() < 0.01),
+            )
+            .or_else(|| oldest(&mut self.voices())),
+            VoiceStealing::ReleasedFirst => oldest(&mut self.voices().filter(|playing| {
+                !playing.is_playing()
+                    || self
+                        .releasing_notes
+                        .iter()
+                        .any(|releasing| releasing.id == playing.id)
+            }))
+            .or_else(|| oldest(&mut self.voices())),
+        };
+
+        if let Some(id) = victim {
+            self.release_where(|playing| playing.id == id);
+            if let Some(stolen) = self
+                .releasing_notes
+                .iter()
+                .find(|releasing| releasing.id == id)
+            {
+                stolen.voice.fade_out(STEAL_FADE);
+            }
+        }
+    }
 
-        let mut controller = InstrumentController::default();
+    /// Releases the playing notes matching `predicate`, keeping track of them
+    /// until they finish sounding.
+    fn release_where<F: Fn(&PlayingNote<T>) -> bool>(&mut self, predicate: F) {
+        self.prune_finished();
+        let (released, playing): (Vec<_>, Vec<_>) = std::mem::take(&mut self.playing_notes)
+            .into_iter()
+            .partition(|playing| predicate(playing));
+        self.playing_notes = playing;
+        for note in released {
+            note.stop();
+            self.releasing_notes.push(note);
+        }
+    }
+
+    /// Forgets released notes that have finished sounding.
+    fn prune_finished(&mut self) {
+        self.releasing_notes.retain(|n| !n.is_finished());
+    }
+
+    /// Plays a single voice at a time. New notes take over from held notes
+    /// without restarting the voice, and releasing a note returns to the most
+    /// recent note still held.
+    pub fn set_mono(&mut self, mono: bool) {
+        self.mono = mono;
+        self.held_notes.clear();
+    }
+
+    /// Enables gliding between notes in mono mode.
+    pub fn set_portamento(&mut self, enabled: bool) {
+        self.portamento = enabled;
+    }
+
+    /// Sets how long portamento takes to slide between notes.
+    pub fn set_glide_time(&mut self, glide_time: Duration) {
+        self.glide_time = glide_time;
+    }
+
+    /// Plays `note`, returning an id that can stop it. Notes can be played
+    /// again while they are already playing.
+    pub fn play_note(&mut self, note: Note) -> Result<NoteId, anyhow::Error> {
+        self.start_note(note, None)
+    }
+
+    /// Plays a MIDI key tuned by the instrument's tuning, replacing any note
+    /// already playing from that key. The note can be stopped with
+    /// [`Self::stop_key`].
+    pub fn play_key(&mut self, key: u8, velocity: u8) -> Result<NoteId, anyhow::Error> {
+        let hertz = self
+            .tuning
+            .hertz(key)
+            .ok_or(tuning::Error::UnmappedKey(key))?;
+        if !self.mono {
+            self.release_where(|n| n.key == Some(key));
+        }
+        self.start_note(Note::from_hertz(hertz, velocity), Some(key))
+    }
+
+    fn start_note(&mut self, note: Note, key: Option<u8>) -> Result<NoteId, anyhow::Error> {
+        self.last_note_id += 1;
+        let id = NoteId(self.last_note_id);
+
+        if self.mono {
+            let held = HeldNote { id, key, note };
+            if !self.held_notes.is_empty() && self.glide_to(note) {
+                self.held_notes.push(held);
+                return Ok(id);
+            }
+
+            self.held_notes.clear();
+            self.held_notes.push(held);
+            self.release_where(|_| true);
+        }
+
+        self.prune_finished();
+        if let Some(max_polyphony) = self.max_polyphony {
+            while self.voice_count() >= max_polyphony.max(1) {
+                self.steal_voice(note);
+            }
+        }
+
+        let mut controller = InstrumentController::new(self.controls.clone());
         let source = self.tone_generator.generate_tone(note, &mut controller)?;
-        let handle = Some(self.device.play(source, note)?);
+        let (source, voice) = Voice::new(source);
+        let handle = self.device.play(source.prepare(), note)?;
+        handle.set_pitch_bend(self.pitch_bend_semitones());
 
         self.playing_notes.push(PlayingNote {
             note,
-            handle,
+            id,
+            key,
+            handle: Some(handle),
             controller,
+            voice,
         });
 
-        Ok(())
+        Ok(id)
+    }
+
+    /// Retunes the mono voice to `note`, returning false if there is no
+    /// voice to retune.
+    fn glide_to(&self, note: Note) -> bool {
+        let glide = if self.portamento {
+            self.glide_time
+        } else {
+            Duration::default()
+        };
+        match self.playing_notes.last() {
+            Some(PlayingNote {
+                note: voice_note,
+                handle: Some(handle),
+                ..
+            }) => {
+                handle.transpose(note.step() - voice_note.step(), glide);
+                true
+            }
+            _ => false,
+        }
     }
 
-    pub fn stop_note(&mut self, step: u8) {
+    /// Stops the notes played by `key` with [`Self::play_key`].
+    pub fn stop_key(&mut self, key: u8) {
+        let ids = if self.mono {
+            self.held_notes
+                .iter()
+                .filter(|held| held.key == Some(key))
+                .map(|held| held.id)
+                .collect::<Vec<_>>()
+        } else {
+            self.playing_notes
+                .iter()
+                .filter(|playing| playing.key == Some(key))
+                .map(|playing| playing.id)
+                .collect()
+        };
+
+        for id in ids {
+            self.stop_note_by_id(id);
+        }
+    }
+
+    pub fn stop_note_by_id(&mut self, id: NoteId) {
+        self.prune_finished();
+        if self.mono {
+            let was_sounding = self.held_notes.last().is_some_and(|held| held.id == id);
+            self.held_notes.retain(|held| held.id != id);
+            match self.held_notes.last() {
+                Some(held) if was_sounding => {
+                    self.glide_to(held.note);
+                }
+                Some(_) => {}
+                None if self.sustain => self.playing_notes.iter().for_each(PlayingNote::sustain),
+                None => self.release_where(|_| true),
+            }
+            return;
+        }
+
         if self.sustain {
             // For sustain, we need ot keep the notes playing, but mark that the key isn't pressed
             // so that when the pedal is released, the note isn't filtered out.
-            if let Some(existing_note) = self
-                .playing_notes
-                .iter_mut()
-                .find(|pn| pn.note.step() as u8 == step)
-            {
+            if let Some(existing_note) = self.playing_notes.iter().find(|pn| pn.id == id) {
                 existing_note.sustain();
             }
         } else {
-            self.playing_notes.retain(|pn| pn.note.step() as u8 != step);
+            self.release_where(|pn| pn.id == id);
+        }
+    }
+
+    /// Sets how far a full pitch bend retunes notes. Defaults to 2 semitones.
+    pub fn set_pitch_bend_range(&mut self, semitones: f32) {
+        self.pitch_bend_range = semitones;
+        self.apply_pitch_bend();
+    }
+
+    /// Bends every playing and future note. `amount` ranges from -1 to 1,
+    /// which is scaled by the pitch bend range.
+    pub fn set_pitch_bend(&mut self, amount: f32) {
+        self.pitch_bend = amount.clamp(-1., 1.);
+        self.apply_pitch_bend();
+    }
+
+    fn pitch_bend_semitones(&self) -> f32 {
+        self.pitch_bend * self.pitch_bend_range
+    }
+
+    fn apply_pitch_bend(&self) {
+        let semitones = self.pitch_bend_semitones();
+        for handle in self
+            .playing_notes
+            .iter()
+            .chain(self.releasing_notes.iter())
+            .filter_map(|n| n.handle.as_ref())
+        {
+            handle.set_pitch_bend(semitones);
         }
     }
 
@@ -224,7 +556,300 @@ where
         self.sustain = active;
 
         if !active {
-            self.playing_notes.retain(|n| n.is_playing());
+            self.release_where(|n| !n.is_playing());
         }
     }
 }
+
+#[cfg(all(test, feature = "serialization"))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::convert::TryInto;
+
+    #[test]
+    fn controls_reach_playing_notes() {
+        let instrument: LoadedInstrument = ron::from_str::<serialization::Instrument>(
+            r#"Instrument(
+                name: "Controlled",
+                envelopes: {},
+                nodes: {
+                    "output": Oscillator(
+                        function: Square,
+                        frequency: Value(1),
+                        amplitude: Multiply([Control("cc1"), Value(2)]),
+                    ),
+                },
+            )"#,
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let mut instrument = VirtualInstrument::new_offline(44_100, instrument);
+        instrument.set_control("cc1", 0.25);
+        instrument.play_note(Note::new(60., 127)).unwrap();
+
+        let level = |instrument: &VirtualInstrument<LoadedInstrument>| {
+            instrument.device().render(1).unwrap()[0].left
+        };
+        assert_eq!(level(&instrument), 0.25);
+        instrument.set_control("cc1", 0.5);
+        assert_eq!(level(&instrument), 0.5);
+        assert_eq!(instrument.controls().get("cc1"), 0.5);
+    }
+
+    #[test]
+    fn one_shot_samples_ignore_note_off() {
+        let dir = TempDir::new("one-shot");
+        dir.write_wav("tone.wav", 1_000, &[0.5; 100]);
+        let load = |one_shot: bool| {
+            let spec = ron::from_str::<serialization::Instrument>(&format!(
+                r#"Instrument(
+                    name: "Sampled",
+                    envelopes: {{
+                        "gate": (sustain: Some(Sustain(1))),
+                    }},
+                    nodes: {{
+                        "output": Sample(
+                            file: "tone.wav",
+                            root_key: 60,
+                            amplitude: Envelope("gate"),
+                            fixed_pitch: true,
+                            one_shot: {},
+                        ),
+                    }},
+                )"#,
+                one_shot
+            ))
+            .unwrap();
+            LoadedInstrument::load(spec, dir.path()).unwrap()
+        };
+        let rendered_after_release = |one_shot| {
+            let mut instrument = VirtualInstrument::new_offline(1_000, load(one_shot));
+            let id = instrument.play_note(Note::new(60., 127)).unwrap();
+            instrument.device().render(10).unwrap();
+            instrument.stop_note_by_id(id);
+            instrument
+                .device()
+                .render(50)
+                .unwrap()
+                .iter()
+                .filter(|sample| sample.left > 0.4)
+                .count()
+        };
+
+        assert!(rendered_after_release(false) < 5);
+        assert_eq!(rendered_after_release(true), 50);
+    }
+
+    fn cycles(instrument: &VirtualInstrument<LoadedInstrument>, duration: Duration) -> usize {
+        instrument
+            .device()
+            .render_duration(duration)
+            .unwrap()
+            .windows(2)
+            .filter(|pair| pair[0].left < 0. && pair[1].left >= 0.)
+            .count()
+    }
+
+    #[test]
+    fn mono_legato_and_portamento() {
+        let instrument: LoadedInstrument = ron::from_str::<serialization::Instrument>(
+            r#"Instrument(
+                name: "Sine",
+                envelopes: {},
+                nodes: {
+                    "output": Oscillator(
+                        function: Sine,
+                        frequency: NoteHertz,
+                        amplitude: Value(1),
+                    ),
+                },
+            )"#,
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let mut instrument = VirtualInstrument::new_offline(44_100, instrument);
+        instrument.set_mono(true);
+        let a3 = Note::from_hertz(220., 127);
+        let a4 = Note::from_hertz(440., 127);
+
+        let a3 = instrument.play_note(a3).unwrap();
+        assert!((21..=22).contains(&cycles(&instrument, Duration::from_millis(100))));
+
+        // Overlapping notes retune the existing voice
+        let a4_id = instrument.play_note(a4).unwrap();
+        assert_eq!(instrument.playing_notes.len(), 1);
+        assert!((43..=44).contains(&cycles(&instrument, Duration::from_millis(100))));
+
+        // Releasing the newest note returns to the held note
+        instrument.stop_note_by_id(a4_id);
+        assert!((21..=22).contains(&cycles(&instrument, Duration::from_millis(100))));
+
+        // With portamento, the pitch slides over the glide time
+        instrument.set_portamento(true);
+        instrument.set_glide_time(Duration::from_millis(100));
+        let a4 = instrument.play_note(a4).unwrap();
+        assert!((30..=36).contains(&cycles(&instrument, Duration::from_millis(100))));
+        assert!((43..=44).contains(&cycles(&instrument, Duration::from_millis(100))));
+
+        instrument.stop_note_by_id(a4);
+        instrument.stop_note_by_id(a3);
+        assert!(instrument.playing_notes.is_empty());
+    }
+
+    fn enveloped_instrument() -> VirtualInstrument<LoadedInstrument> {
+        let instrument: LoadedInstrument = ron::from_str::<serialization::Instrument>(
+            r#"Instrument(
+                name: "Enveloped",
+                envelopes: {
+                    "main": (
+                        sustain: Some(Sustain(1.0)),
+                        release: Some(Milliseconds(500)),
+                    ),
+                },
+                nodes: {
+                    "sine": Oscillator(
+                        function: Sine,
+                        frequency: NoteHertz,
+                        amplitude: Multiply([NoteVelocity, Value(2)]),
+                    ),
+                    "output": Amplify(value: Envelope("main"), input: "sine"),
+                },
+            )"#,
+        )
+        .unwrap()
+        .try_into()
+        .unwrap();
+        let mut instrument = VirtualInstrument::new_offline(44_100, instrument);
+        instrument.set_max_polyphony(Some(2));
+        instrument
+    }
+
+    fn playing_keys(instrument: &VirtualInstrument<LoadedInstrument>) -> Vec<u8> {
+        let mut keys = instrument
+            .voices()
+            .map(|playing| playing.note.step().round() as u8)
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn voice_stealing() {
+        let play = |instrument: &mut VirtualInstrument<LoadedInstrument>, key, velocity| {
+            instrument.play_key(key, velocity).unwrap();
+            instrument.device().render(441).unwrap();
+        };
+
+        let mut instrument = enveloped_instrument();
+        play(&mut instrument, 60, 127);
+        play(&mut instrument, 62, 127);
+        play(&mut instrument, 64, 127);
+        assert_eq!(playing_keys(&instrument), vec![62, 64]);
+
+        let mut instrument = enveloped_instrument();
+        instrument.set_voice_stealing(VoiceStealing::Quietest);
+        play(&mut instrument, 60, 127);
+        play(&mut instrument, 62, 20);
+        play(&mut instrument, 64, 127);
+        assert_eq!(playing_keys(&instrument), vec![60, 64]);
+
+        let mut instrument = enveloped_instrument();
+        instrument.set_voice_stealing(VoiceStealing::ReleasedFirst);
+        play(&mut instrument, 60, 127);
+        play(&mut instrument, 62, 127);
+        instrument.stop_key(62);
+        play(&mut instrument, 64, 127);
+        assert_eq!(playing_keys(&instrument), vec![60, 64]);
+
+        let mut instrument = enveloped_instrument();
+        instrument.set_voice_stealing(VoiceStealing::SameNote);
+        instrument.set_max_polyphony(Some(3));
+        play(&mut instrument, 60, 127);
+        play(&mut instrument, 62, 127);
+        instrument.stop_key(62);
+        play(&mut instrument, 64, 127);
+        play(&mut instrument, 62, 127);
+        assert_eq!(playing_keys(&instrument), vec![60, 62, 64]);
+        assert_eq!(instrument.voice_count(), 3);
+
+        // Stolen voices fade out rather than stopping abruptly
+        instrument.play_key(65, 127).unwrap();
+        let stolen = instrument
+            .releasing_notes
+            .iter()
+            .find(|playing| playing.voice.is_fading())
+            .unwrap();
+        assert!(!stolen.voice.finished());
+        instrument.device().render(441).unwrap();
+        assert!(instrument
+            .releasing_notes
+            .iter()
+            .filter(|playing| playing.voice.is_fading())
+            .all(|playing| playing.voice.finished()));
+    }
+
+    #[test]
+    fn notes_are_stopped_by_id() {
+        let mut instrument = enveloped_instrument();
+        instrument.set_max_polyphony(None);
+        let first = instrument.play_note(Note::new(60.5, 127)).unwrap();
+        let second = instrument.play_note(Note::new(60.5, 127)).unwrap();
+        let keyed = instrument.play_key(60, 127).unwrap();
+        assert_eq!(instrument.playing_notes.len(), 3);
+
+        // Keys only match notes played by key
+        instrument.stop_key(60);
+        assert_eq!(
+            instrument
+                .playing_notes
+                .iter()
+                .map(|playing| playing.id)
+                .collect::<Vec<_>>(),
+            vec![first, second]
+        );
+        assert!(instrument.releasing_notes.iter().any(|n| n.id == keyed));
+
+        instrument.stop_note_by_id(first);
+        assert_eq!(instrument.playing_notes.len(), 1);
+        assert_eq!(instrument.playing_notes[0].id, second);
+
+        // Released notes are forgotten once they finish sounding
+        instrument
+            .device()
+            .render_duration(Duration::from_millis(600))
+            .unwrap();
+        instrument.stop_note_by_id(second);
+        assert_eq!(
+            instrument
+                .releasing_notes
+                .iter()
+                .map(|releasing| releasing.id)
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+    }
+
+    #[test]
+    fn keys_follow_the_tuning() {
+        let mut instrument = enveloped_instrument();
+        instrument.set_max_polyphony(None);
+        let hertz = |instrument: &VirtualInstrument<LoadedInstrument>| {
+            instrument.playing_notes.last().unwrap().note.hertz()
+        };
+        instrument.play_key(69, 127).unwrap();
+        assert_eq!(hertz(&instrument), 440.);
+
+        instrument.set_tuning(EqualDivision::default().with_reference(69, 432.));
+        instrument.play_key(81, 127).unwrap();
+        assert_eq!(hertz(&instrument), 864.);
+
+        instrument.set_tuning(tuning::ScalaTuning::new(
+            tuning::ScalaScale::parse("Octaves\n1\n2/1\n").unwrap(),
+            tuning::KeyboardMapping::parse("1\n0\n127\n60\n60\n100\n1\nx\n").unwrap(),
+        ));
+        assert!(instrument.play_key(60, 127).is_err());
+    }
+}
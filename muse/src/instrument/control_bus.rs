@@ -0,0 +1,49 @@
+use crossbeam::atomic::AtomicCell;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+pub type ControlValue = Arc<AtomicCell<f32>>;
+
+/// Named values shared between an instrument and whatever is controlling it.
+/// Parameters look up their control once when a note starts and read it
+/// without locking afterwards, so changes reach notes that are already
+/// playing. Controls start at 0.
+///
+/// The MIDI examples use `ccN` for controller N, `pitch_bend` and
+/// `channel_pressure`. Controllers and pressure range from 0 to 1, and pitch
+/// bend ranges from -1 to 1.
+#[derive(Debug, Default, Clone)]
+pub struct ControlBus(Arc<RwLock<HashMap<String, ControlValue>>>);
+
+impl ControlBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn control(&self, name: &str) -> ControlValue {
+        if let Some(value) = self.0.read().unwrap().get(name) {
+            return value.clone();
+        }
+
+        let mut controls = self.0.write().unwrap();
+        controls
+            .entry(name.to_owned())
+            .or_insert_with(|| Arc::new(AtomicCell::new(0.)))
+            .clone()
+    }
+
+    pub fn get(&self, name: &str) -> f32 {
+        self.0
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|value| value.load())
+            .unwrap_or_default()
+    }
+
+    pub fn set(&self, name: &str, value: f32) {
+        self.control(name).store(value);
+    }
+}
@@ -1,7 +1,13 @@
+use crate::{
+    envelope::EnvelopeScaling,
+    lfo::LfoWaveform,
+    sampler::{self, FilterMode, LoopPoints, NoiseColor},
+};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 mod loader;
+pub mod sfz;
 pub use loader::*;
 
 #[derive(thiserror::Error, Debug)]
@@ -14,6 +20,20 @@ pub enum Error {
     RecursiveNodeDependencies(Vec<String>),
     #[error("error with envelope curve: {0}")]
     EnvelopeCurveError(#[from] crate::envelope::EnvelopeCurveError),
+    #[error("error reading file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("error parsing instrument: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("error reading audio file: {0}")]
+    Wav(#[from] crate::wav::Error),
+    #[error("invalid range for {parameter}: {min} to {max}")]
+    InvalidRange {
+        parameter: &'static str,
+        min: f32,
+        max: f32,
+    },
+    #[error("error parsing sfz on line {line}: {message}")]
+    Sfz { line: usize, message: String },
     #[error("error loading node {0:?}")]
     Unknown(#[from] anyhow::Error),
 }
@@ -32,13 +52,34 @@ pub struct Envelope {
     pub decay: Option<EnvelopeCurve>,
     pub sustain: Option<EnvelopeCurve>,
     pub release: Option<EnvelopeCurve>,
+    #[serde(default)]
+    pub scaling: EnvelopeScaling,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum EnvelopeCurve {
     Milliseconds(u32),
     Sustain(f32),
-    // https://github.com/khonsulabs/muse/issues/16
+    /// Straight lines between `(milliseconds, value)` points.
+    Points(Vec<(f32, f32)>),
+    /// Lines and cubic bezier curves starting from a `(milliseconds, value)`
+    /// point.
+    Path {
+        start: (f32, f32),
+        segments: Vec<CurveSegment>,
+    },
+}
+
+/// A segment of an [`EnvelopeCurve::Path`]. Points are
+/// `(milliseconds, value)`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum CurveSegment {
+    Line((f32, f32)),
+    Cubic {
+        to: (f32, f32),
+        start_control: (f32, f32),
+        end_control: (f32, f32),
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -48,6 +89,56 @@ pub enum Parameter {
     NoteHertz,
     NoteStep,
     Envelope(String),
+    Lfo(Lfo),
+    /// A live value from the instrument's control bus.
+    Control(String),
+    Add(Vec<Parameter>),
+    Multiply(Vec<Parameter>),
+    Min(Vec<Parameter>),
+    Max(Vec<Parameter>),
+    /// Maps `input` from 0..1 onto `min..max`.
+    Scale {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    /// Maps `input` from 0..1 onto `min..max` exponentially. `min` and `max`
+    /// must be non-zero with the same sign.
+    Exponential {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    /// Limits `input` to `min..=max`, where `min` is at most `max`.
+    Clamp {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Lfo {
+    #[serde(default)]
+    pub waveform: LfoWaveform,
+    /// The frequency in hertz.
+    pub rate: f32,
+    #[serde(default = "default_depth")]
+    pub depth: f32,
+    #[serde(default)]
+    pub offset: f32,
+    /// Milliseconds to wait before the LFO starts.
+    #[serde(default)]
+    pub delay: Option<u32>,
+    /// Milliseconds for the depth to ramp up after the delay.
+    #[serde(default)]
+    pub fade_in: Option<u32>,
+    #[serde(default)]
+    pub key_sync: bool,
+}
+
+fn default_depth() -> f32 {
+    1.
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -56,6 +147,76 @@ pub enum Node<T> {
         function: OscillatorFunction,
         frequency: Parameter,
         amplitude: Parameter,
+        #[serde(default)]
+        start_phase: StartPhase,
+    },
+    Pulse {
+        frequency: Parameter,
+        amplitude: Parameter,
+        width: Parameter,
+        #[serde(default)]
+        start_phase: StartPhase,
+    },
+    Noise {
+        color: NoiseColor,
+        amplitude: Parameter,
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    /// Morphs between single-cycle waveforms loaded from WAV files. Paths
+    /// are relative to the instrument file.
+    Wavetable {
+        files: Vec<String>,
+        frequency: Parameter,
+        amplitude: Parameter,
+        morph: Parameter,
+        #[serde(default)]
+        start_phase: StartPhase,
+    },
+    /// Plays back a WAV file, relative to the instrument file.
+    Sample {
+        file: String,
+        root_key: u8,
+        amplitude: Parameter,
+        #[serde(default)]
+        loop_points: Option<LoopPoints>,
+        /// Keeps looping after the note is released, rather than playing the
+        /// rest of the sample.
+        #[serde(default)]
+        loop_through_release: bool,
+        #[serde(default)]
+        one_shot: bool,
+        /// Plays the sample at its recorded pitch regardless of the note.
+        #[serde(default)]
+        fixed_pitch: bool,
+        /// An offset in cents.
+        #[serde(default)]
+        tune: f32,
+    },
+    /// Plays the zones matching each note's key and velocity.
+    Zoned {
+        zones: Vec<Zone>,
+    },
+    /// A resonant filter. `resonance` is the filter's Q, where 0.707 has no
+    /// resonant peak.
+    Filter {
+        #[serde(default)]
+        design: FilterDesign,
+        mode: FilterMode,
+        cutoff: Parameter,
+        #[serde(default = "default_resonance")]
+        resonance: Parameter,
+        input: String,
+    },
+    /// A four-pole low-pass filter modeled on the Moog ladder. `resonance`
+    /// ranges from 0 to 1, and the filter oscillates at its cutoff above 1.
+    Ladder {
+        cutoff: Parameter,
+        #[serde(default = "default_ladder_resonance")]
+        resonance: Parameter,
+        #[serde(default = "default_drive")]
+        drive: Parameter,
+        input: String,
     },
     Amplify {
         value: Parameter,
@@ -85,4 +246,71 @@ pub enum OscillatorFunction {
     Sine,
     Square,
     Triangle,
+    BandLimitedSawtooth,
+    BandLimitedSquare,
+    BandLimitedTriangle,
+}
+
+/// Where in its cycle an oscillator begins.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum StartPhase {
+    #[default]
+    Zero,
+    /// A fraction of a cycle, from 0 to 1.
+    Fixed(f32),
+    /// A different random phase for every note.
+    Random,
+}
+
+impl From<StartPhase> for sampler::StartPhase {
+    fn from(start_phase: StartPhase) -> Self {
+        match start_phase {
+            StartPhase::Zero => Self::Zero,
+            StartPhase::Fixed(phase) => Self::Fixed(phase),
+            StartPhase::Random => Self::Random,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub enum FilterDesign {
+    #[default]
+    StateVariable,
+    Biquad,
+}
+
+fn default_resonance() -> Parameter {
+    Parameter::Value(std::f32::consts::FRAC_1_SQRT_2)
+}
+
+fn default_ladder_resonance() -> Parameter {
+    Parameter::Value(0.)
+}
+
+fn default_drive() -> Parameter {
+    Parameter::Value(1.)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Zone {
+    pub input: String,
+    /// The inclusive range of MIDI keys this zone plays for.
+    #[serde(default = "full_range")]
+    pub keys: (u8, u8),
+    #[serde(default = "full_range")]
+    pub velocities: (u8, u8),
+    #[serde(default)]
+    pub round_robin: Option<RoundRobin>,
+}
+
+fn full_range() -> (u8, u8) {
+    (0, 127)
+}
+
+/// Alternates between zones on successive notes. A zone plays on every
+/// `length`th note, starting with the note at `position`, counting from 1.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct RoundRobin {
+    pub position: usize,
+    pub length: usize,
 }
@@ -1,24 +1,75 @@
 use crate::{
     envelope::EnvelopeConfiguration,
     instrument::serialization::{self, Error, Node},
+    lfo::LfoConfiguration,
     node,
+    sampler::WavetableData,
+    wav::{self, WavData},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
-use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct Context<'a, T> {
     envelopes: &'a HashMap<String, EnvelopeConfiguration>,
     nodes: HashMap<String, node::Node<T>>,
+    base_path: &'a Path,
+    /// Files already read, so nodes retried in a later pass don't read them
+    /// again.
+    samples: HashMap<PathBuf, Arc<WavData>>,
+    wavetables: HashMap<Vec<PathBuf>, Arc<WavetableData>>,
 }
 
 impl<'a, T> Context<'a, T> {
-    pub(crate) fn new(envelopes: &'a HashMap<String, EnvelopeConfiguration>) -> Self {
+    pub(crate) fn new(
+        envelopes: &'a HashMap<String, EnvelopeConfiguration>,
+        base_path: &'a Path,
+    ) -> Self {
         Self {
             envelopes,
             nodes: HashMap::new(),
+            base_path,
+            samples: HashMap::new(),
+            wavetables: HashMap::new(),
         }
     }
 
+    /// Resolves a path from the instrument relative to the instrument's file.
+    pub fn resolve_path(&self, path: &str) -> PathBuf {
+        self.base_path.join(path)
+    }
+
+    /// Reads a WAV file relative to the instrument, reusing it if it has
+    /// already been read.
+    pub fn sample(&mut self, file: &str) -> Result<Arc<WavData>, Error> {
+        let path = self.resolve_path(file);
+        if let Some(data) = self.samples.get(&path) {
+            return Ok(data.clone());
+        }
+        let data = Arc::new(wav::read(&path)?);
+        self.samples.insert(path, data.clone());
+        Ok(data)
+    }
+
+    /// Loads the frames of a wavetable, reusing them if they have already
+    /// been loaded.
+    pub fn wavetable(&mut self, files: &[String]) -> Result<Arc<WavetableData>, Error> {
+        let paths = files
+            .iter()
+            .map(|file| self.resolve_path(file))
+            .collect::<Vec<_>>();
+        if let Some(table) = self.wavetables.get(&paths) {
+            return Ok(table.clone());
+        }
+        let table = Arc::new(WavetableData::load(&paths)?);
+        self.wavetables.insert(paths, table.clone());
+        Ok(table)
+    }
+
     pub fn envelope(&self, name: &str) -> Result<EnvelopeConfiguration, Error> {
         self.envelopes
             .get(name)
@@ -73,10 +124,76 @@ impl<'a, T> Context<'a, T> {
             serialization::Parameter::NoteHertz => node::Parameter::NoteHertz,
             serialization::Parameter::NoteStep => node::Parameter::NoteStep,
             serialization::Parameter::NoteVelocity => node::Parameter::NoteVelocity,
+            serialization::Parameter::Lfo(lfo) => node::Parameter::Lfo(LfoConfiguration {
+                waveform: lfo.waveform,
+                rate: lfo.rate,
+                depth: lfo.depth,
+                offset: lfo.offset,
+                delay: lfo.delay.map(|ms| Duration::from_millis(ms as u64)),
+                fade_in: lfo.fade_in.map(|ms| Duration::from_millis(ms as u64)),
+                key_sync: lfo.key_sync,
+            }),
+            serialization::Parameter::Control(name) => node::Parameter::Control(name.clone()),
+            serialization::Parameter::Add(inputs) => {
+                node::Parameter::Add(self.load_parameters(inputs)?)
+            }
+            serialization::Parameter::Multiply(inputs) => {
+                node::Parameter::Multiply(self.load_parameters(inputs)?)
+            }
+            serialization::Parameter::Min(inputs) => {
+                node::Parameter::Min(self.load_parameters(inputs)?)
+            }
+            serialization::Parameter::Max(inputs) => {
+                node::Parameter::Max(self.load_parameters(inputs)?)
+            }
+            serialization::Parameter::Scale { input, min, max } => node::Parameter::Scale {
+                input: Box::new(self.load_parameter(input)?),
+                min: *min,
+                max: *max,
+            },
+            serialization::Parameter::Exponential { input, min, max } => {
+                // Anything else produces NaN or infinity
+                if !(min.is_finite() && max.is_finite() && min * max > 0.) {
+                    return Err(Error::InvalidRange {
+                        parameter: "Exponential",
+                        min: *min,
+                        max: *max,
+                    });
+                }
+                node::Parameter::Exponential {
+                    input: Box::new(self.load_parameter(input)?),
+                    min: *min,
+                    max: *max,
+                }
+            }
+            serialization::Parameter::Clamp { input, min, max } => {
+                if min.is_nan() || max.is_nan() || min > max {
+                    return Err(Error::InvalidRange {
+                        parameter: "Clamp",
+                        min: *min,
+                        max: *max,
+                    });
+                }
+                node::Parameter::Clamp {
+                    input: Box::new(self.load_parameter(input)?),
+                    min: *min,
+                    max: *max,
+                }
+            }
         };
 
         Ok(parameter)
     }
+
+    pub fn load_parameters(
+        &mut self,
+        parameters: &[serialization::Parameter],
+    ) -> Result<Vec<node::Parameter>, Error> {
+        parameters
+            .iter()
+            .map(|parameter| self.load_parameter(parameter))
+            .collect()
+    }
 }
 
 pub trait NodeInstantiator<T> {
@@ -108,10 +225,106 @@ where
                 function,
                 frequency,
                 amplitude,
+                start_phase,
             } => Ok(node::Node::Oscillator {
                 function: *function,
                 frequency: context.load_parameter(frequency)?,
                 amplitude: context.load_parameter(amplitude)?,
+                start_phase: (*start_phase).into(),
+            }),
+            Node::Pulse {
+                frequency,
+                amplitude,
+                width,
+                start_phase,
+            } => Ok(node::Node::Pulse {
+                frequency: context.load_parameter(frequency)?,
+                amplitude: context.load_parameter(amplitude)?,
+                width: context.load_parameter(width)?,
+                start_phase: (*start_phase).into(),
+            }),
+            Node::Noise {
+                color,
+                amplitude,
+                seed,
+            } => Ok(node::Node::Noise {
+                color: *color,
+                amplitude: context.load_parameter(amplitude)?,
+                seed: *seed,
+                instances: Default::default(),
+            }),
+            Node::Wavetable {
+                files,
+                frequency,
+                amplitude,
+                morph,
+                start_phase,
+            } => Ok(node::Node::Wavetable {
+                table: context.wavetable(files)?,
+                frequency: context.load_parameter(frequency)?,
+                amplitude: context.load_parameter(amplitude)?,
+                morph: context.load_parameter(morph)?,
+                start_phase: (*start_phase).into(),
+            }),
+            Node::Sample {
+                file,
+                root_key,
+                amplitude,
+                loop_points,
+                loop_through_release,
+                one_shot,
+                fixed_pitch,
+                tune,
+            } => Ok(node::Node::Sample {
+                data: context.sample(file)?,
+                root_key: *root_key,
+                amplitude: context.load_parameter(amplitude)?,
+                loop_points: *loop_points,
+                loop_through_release: *loop_through_release,
+                one_shot: *one_shot,
+                pitch_tracking: !fixed_pitch,
+                tune: *tune,
+            }),
+            Node::Zoned { zones } => {
+                let inputs = zones
+                    .iter()
+                    .map(|zone| zone.input.clone())
+                    .collect::<Vec<_>>();
+                let zones = zones
+                    .iter()
+                    .zip(context.node_references(&inputs)?)
+                    .map(|(zone, node)| node::Zone {
+                        keys: zone.keys.0..=zone.keys.1,
+                        velocities: zone.velocities.0..=zone.velocities.1,
+                        round_robin: zone.round_robin,
+                        node,
+                    })
+                    .collect();
+                Ok(node::Node::zoned(zones))
+            }
+            Node::Filter {
+                design,
+                mode,
+                cutoff,
+                resonance,
+                input,
+            } => Ok(node::Node::Filter {
+                design: *design,
+                mode: *mode,
+                cutoff: context.load_parameter(cutoff)?,
+                resonance: context.load_parameter(resonance)?,
+                input: Box::new(context.node_reference(input)?),
+            }),
+            Node::Ladder {
+                cutoff,
+                resonance,
+                drive,
+                input,
+            } => Ok(node::Node::Ladder {
+                cutoff: context.load_parameter(cutoff)?,
+                resonance: context.load_parameter(resonance)?,
+                drive: context.load_parameter(drive)?,
+                input: Box::new(context.node_reference(input)?),
             }),
             Node::Multiply { inputs } => Ok(node::Node::Multiply {
                 inputs: context.node_references(inputs)?,
@@ -0,0 +1,400 @@
+//! Converts a subset of the [SFZ format](https://sfzformat.com) into an
+//! [`Instrument`].
+//!
+//! Supported headers are `<control>`, `<global>`, `<master>`, `<group>` and
+//! `<region>`. Supported opcodes are `default_path`, `sample`, `key`, `lokey`,
+//! `hikey`, `pitch_keycenter`, `pitch_keytrack`, `tune`, `lovel`, `hivel`,
+//! `loop_mode`, `loop_start`, `loop_end`, `seq_length`, `seq_position` and the
+//! `ampeg_attack`, `ampeg_hold`, `ampeg_decay`, `ampeg_sustain` and
+//! `ampeg_release` envelope. Anything else, including other headers and the
+//! opcodes beneath them, is ignored, and unknown loop modes don't loop.
+//! `loop_continuous` keeps looping after the note is released, while
+//! `loop_sustain` only loops while it is held.
+
+use crate::{
+    instrument::serialization::{
+        Envelope, EnvelopeCurve, Error, Instrument, Node, Parameter, RoundRobin, Zone,
+    },
+    sampler::LoopPoints,
+    Note,
+};
+use std::{collections::HashMap, str::FromStr};
+
+type Opcodes = HashMap<String, (usize, String)>;
+
+/// Parses SFZ `source`. Sample paths are left relative to the SFZ file.
+pub fn parse<T>(source: &str, name: &str) -> Result<Instrument<T>, Error> {
+    let mut control = Opcodes::new();
+    let mut global = Opcodes::new();
+    let mut master = Opcodes::new();
+    let mut group = Opcodes::new();
+    let mut regions = Vec::new();
+    let mut current: Option<String> = None;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.split("//").next().unwrap_or_default();
+        let mut last_opcode: Option<String> = None;
+        for token in tokens(line) {
+            match token {
+                Token::Header(header) => {
+                    // Starting a header clears anything beneath it in the hierarchy
+                    match header {
+                        "global" => {
+                            global.clear();
+                            master.clear();
+                            group.clear();
+                        }
+                        "master" => {
+                            master.clear();
+                            group.clear();
+                        }
+                        "group" => group.clear(),
+                        "region" => {
+                            // Regions inherit from their parents, but their
+                            // own opcodes take priority.
+                            let mut region = global.clone();
+                            region.extend(master.clone());
+                            region.extend(group.clone());
+                            regions.push(region);
+                        }
+                        _ => {}
+                    }
+                    current = Some(header.to_owned());
+                    last_opcode = None;
+                }
+                Token::Word(word) => {
+                    let opcodes = match current.as_deref() {
+                        Some("control") => &mut control,
+                        Some("global") => &mut global,
+                        Some("master") => &mut master,
+                        Some("group") => &mut group,
+                        Some("region") => regions.last_mut().unwrap(),
+                        // Opcodes of unsupported headers such as <curve>
+                        Some(_) => continue,
+                        None => {
+                            return Err(Error::Sfz {
+                                line: line_number,
+                                message: format!("opcode {:?} found before any header", word),
+                            })
+                        }
+                    };
+
+                    if let Some((opcode, value)) = word.split_once('=') {
+                        opcodes.insert(opcode.to_owned(), (line_number, value.to_owned()));
+                        last_opcode = Some(opcode.to_owned());
+                    } else if let Some((_, value)) = last_opcode
+                        .as_ref()
+                        .and_then(|opcode| opcodes.get_mut(opcode))
+                    {
+                        // Sample names may contain spaces
+                        value.push(' ');
+                        value.push_str(word);
+                    } else {
+                        return Err(Error::Sfz {
+                            line: line_number,
+                            message: format!("expected an opcode, found {:?}", word),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    let default_path = control
+        .get("default_path")
+        .map(|(_, path)| path.replace('\\', "/"))
+        .unwrap_or_default();
+
+    let mut instrument = Instrument {
+        name: name.to_owned(),
+        envelopes: HashMap::new(),
+        nodes: HashMap::new(),
+    };
+    let mut zones = Vec::new();
+    for (index, region) in regions.into_iter().enumerate() {
+        let node_name = format!("region-{}", index);
+        let region = Region(region);
+        zones.push(region.zone(&node_name)?);
+
+        let amplitude = match region.envelope()? {
+            Some(envelope) => {
+                instrument.envelopes.insert(node_name.clone(), envelope);
+                Parameter::Envelope(node_name.clone())
+            }
+            None => Parameter::Value(1.),
+        };
+        instrument
+            .nodes
+            .insert(node_name, region.sample(&default_path, amplitude)?);
+    }
+
+    instrument
+        .nodes
+        .insert("zones".to_owned(), Node::Zoned { zones });
+    instrument.nodes.insert(
+        "output".to_owned(),
+        Node::Amplify {
+            value: Parameter::NoteVelocity,
+            input: "zones".to_owned(),
+        },
+    );
+    Ok(instrument)
+}
+
+enum Token<'a> {
+    Header(&'a str),
+    Word(&'a str),
+}
+
+fn tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut remaining = line;
+    while let Some(start) = remaining.find('<') {
+        tokens.extend(remaining[..start].split_whitespace().map(Token::Word));
+        let header = &remaining[start + 1..];
+        let end = header.find('>').unwrap_or(header.len());
+        tokens.push(Token::Header(header[..end].trim()));
+        remaining = header.get(end + 1..).unwrap_or_default();
+    }
+    tokens.extend(remaining.split_whitespace().map(Token::Word));
+    tokens
+}
+
+/// Parses a MIDI key, either as a number or a name like `c#4`, where `c4` is 60.
+pub fn parse_key(value: &str) -> Option<u8> {
+    match value.parse() {
+        Ok(key) => Some(key),
+        Err(_) => Note::from_str(value).ok().and_then(|note| note.key()),
+    }
+}
+
+struct Region(Opcodes);
+
+impl Region {
+    fn error(&self, opcode: &str, message: &str) -> Error {
+        Error::Sfz {
+            line: self
+                .0
+                .get(opcode)
+                .map(|(line, _)| *line)
+                .unwrap_or_default(),
+            message: format!("{}: {}", opcode, message),
+        }
+    }
+
+    fn value(&self, opcode: &str) -> Option<&str> {
+        self.0.get(opcode).map(|(_, value)| value.as_str())
+    }
+
+    fn key(&self, opcode: &str) -> Result<Option<u8>, Error> {
+        self.value(opcode)
+            .map(|value| parse_key(value).ok_or_else(|| self.error(opcode, "invalid key")))
+            .transpose()
+    }
+
+    fn number<N: std::str::FromStr>(&self, opcode: &str) -> Result<Option<N>, Error> {
+        self.value(opcode)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| self.error(opcode, "invalid number"))
+            })
+            .transpose()
+    }
+
+    fn zone(&self, node_name: &str) -> Result<Zone, Error> {
+        let key = self.key("key")?;
+        let round_robin = match self.number::<usize>("seq_length")? {
+            Some(length) if length > 1 => Some(RoundRobin {
+                position: self.number("seq_position")?.unwrap_or(1),
+                length,
+            }),
+            _ => None,
+        };
+
+        Ok(Zone {
+            input: node_name.to_owned(),
+            keys: (
+                self.key("lokey")?.or(key).unwrap_or(0),
+                self.key("hikey")?.or(key).unwrap_or(127),
+            ),
+            velocities: (
+                self.number("lovel")?.unwrap_or(0),
+                self.number("hivel")?.unwrap_or(127),
+            ),
+            round_robin,
+        })
+    }
+
+    fn sample<T>(&self, default_path: &str, amplitude: Parameter) -> Result<Node<T>, Error> {
+        let sample = self
+            .value("sample")
+            .ok_or_else(|| self.error("sample", "every region needs a sample"))?;
+        let root_key = self
+            .key("pitch_keycenter")?
+            .or(self.key("key")?)
+            .unwrap_or(60);
+        let loop_mode = self.value("loop_mode");
+        let (loop_points, one_shot) = match loop_mode {
+            Some("loop_continuous") | Some("loop_sustain") => {
+                let start = self.number("loop_start")?.unwrap_or(0);
+                let loop_points = self.number::<usize>("loop_end")?.map(|end| LoopPoints {
+                    start,
+                    end: end + 1,
+                });
+                (loop_points, false)
+            }
+            Some("one_shot") => (None, true),
+            _ => (None, false),
+        };
+
+        Ok(Node::Sample {
+            file: format!("{}{}", default_path, sample.replace('\\', "/")),
+            root_key,
+            amplitude,
+            loop_points,
+            loop_through_release: loop_mode == Some("loop_continuous"),
+            one_shot,
+            fixed_pitch: self.number::<f32>("pitch_keytrack")? == Some(0.),
+            tune: self.number("tune")?.unwrap_or_default(),
+        })
+    }
+
+    fn envelope(&self) -> Result<Option<Envelope>, Error> {
+        if !self.0.keys().any(|opcode| opcode.starts_with("ampeg_")) {
+            return Ok(None);
+        }
+
+        let milliseconds = |opcode: &str| -> Result<Option<EnvelopeCurve>, Error> {
+            Ok(self
+                .number::<f32>(opcode)?
+                .map(|seconds| EnvelopeCurve::Milliseconds((seconds * 1000.).round() as u32)))
+        };
+        Ok(Some(Envelope {
+            attack: milliseconds("ampeg_attack")?,
+            hold: milliseconds("ampeg_hold")?,
+            decay: milliseconds("ampeg_decay")?,
+            sustain: Some(EnvelopeCurve::Sustain(
+                self.number::<f32>("ampeg_sustain")?.unwrap_or(100.) / 100.,
+            )),
+            release: milliseconds("ampeg_release")?,
+            scaling: Default::default(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names() {
+        assert_eq!(parse_key("60"), Some(60));
+        assert_eq!(parse_key("c4"), Some(60));
+        assert_eq!(parse_key("C#4"), Some(61));
+        assert_eq!(parse_key("eb3"), Some(51));
+        assert_eq!(parse_key("c-1"), Some(0));
+        assert_eq!(parse_key("C##4"), Some(62));
+        assert_eq!(parse_key("h2"), None);
+    }
+
+    #[test]
+    fn regions_inherit_opcodes() {
+        let instrument = parse::<()>(
+            r"
+            <control> default_path=samples\
+            // Comments are ignored
+            <group> lovel=64 ampeg_release=0.5 loop_mode=loop_sustain loop_start=10 loop_end=99
+            <region> sample=Piano C4.wav key=c4
+            <region> sample=piano d4.wav lokey=61 hikey=63 pitch_keycenter=62 seq_length=2 seq_position=2
+            <group>
+            <region> sample=kick.wav key=36 loop_mode=one_shot pitch_keytrack=0
+            ",
+            "piano",
+        )
+        .unwrap();
+
+        assert!(instrument.envelopes.contains_key("region-0"));
+        assert!(!instrument.envelopes.contains_key("region-2"));
+        match &instrument.nodes["zones"] {
+            Node::Zoned { zones } => {
+                assert_eq!(zones.len(), 3);
+                assert_eq!(zones[0].keys, (60, 60));
+                assert_eq!(zones[0].velocities, (64, 127));
+                assert_eq!(zones[1].keys, (61, 63));
+                assert_eq!(
+                    zones[1].round_robin,
+                    Some(RoundRobin {
+                        position: 2,
+                        length: 2
+                    })
+                );
+                assert_eq!(zones[2].velocities, (0, 127));
+            }
+            other => unreachable!("unexpected node {:?}", other),
+        }
+        match &instrument.nodes["region-0"] {
+            Node::Sample {
+                file,
+                root_key,
+                loop_points,
+                ..
+            } => {
+                assert_eq!(file, "samples/Piano C4.wav");
+                assert_eq!(*root_key, 60);
+                assert_eq!(
+                    *loop_points,
+                    Some(LoopPoints {
+                        start: 10,
+                        end: 100
+                    })
+                );
+            }
+            other => unreachable!("unexpected node {:?}", other),
+        }
+        match &instrument.nodes["region-2"] {
+            Node::Sample {
+                one_shot,
+                fixed_pitch,
+                ..
+            } => assert!(*one_shot && *fixed_pitch),
+            other => unreachable!("unexpected node {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_report_lines() {
+        assert!(matches!(
+            parse::<()>("<region>\nsample=a.wav key=zz", "bad"),
+            Err(Error::Sfz { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse::<()>("sample=a.wav", "bad"),
+            Err(Error::Sfz { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn unsupported_headers_are_skipped() {
+        let instrument = parse::<()>(
+            r"
+            <curve> curve_index=7 v000=0 v127=1
+            <effect> type=reverb
+            <region> sample=a.wav loop_mode=loop_backwards
+            <midi> hint_ram_based=1
+            ",
+            "skipping",
+        )
+        .unwrap();
+
+        match &instrument.nodes["region-0"] {
+            Node::Sample {
+                loop_points,
+                one_shot,
+                ..
+            } => assert!(loop_points.is_none() && !*one_shot),
+            other => unreachable!("unexpected node {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::sampler::{FrameInfo, PreparedSampler, Sample, Sampler};
+use crossbeam::atomic::AtomicCell;
+use std::{sync::Arc, time::Duration};
+
+/// How quickly a voice's measured level falls after its peaks.
+const LEVEL_DECAY: Duration = Duration::from_millis(50);
+
+/// Chooses which voice to stop when an instrument reaches its polyphony
+/// limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VoiceStealing {
+    /// Steals the voice that started first.
+    #[default]
+    Oldest,
+    /// Steals the voice with the lowest output level.
+    Quietest,
+    /// Steals a voice playing the same note, falling back to the oldest.
+    SameNote,
+    /// Steals the oldest voice whose key has been released, falling back to
+    /// the oldest.
+    ReleasedFirst,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct VoiceState {
+    level: AtomicCell<f32>,
+    fading: AtomicCell<bool>,
+    /// The seconds to fade out over, once `fading` is set.
+    fade_out: AtomicCell<f32>,
+    finished: AtomicCell<bool>,
+}
+
+impl VoiceState {
+    pub fn level(&self) -> f32 {
+        self.level.load()
+    }
+
+    /// Fades the voice to silence over `duration` and then ends it.
+    pub fn fade_out(&self, duration: Duration) {
+        self.fade_out.store(duration.as_secs_f32());
+        self.fading.store(true);
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fading.load()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished.load()
+    }
+}
+
+/// Wraps each note an instrument plays to measure its level and to fade it
+/// out when it is stolen.
+#[derive(Debug)]
+pub(crate) struct Voice {
+    source: PreparedSampler,
+    state: Arc<VoiceState>,
+    gain: f32,
+}
+
+impl Voice {
+    pub fn new(source: PreparedSampler) -> (Self, Arc<VoiceState>) {
+        let state = Arc::new(VoiceState::default());
+        (
+            Self {
+                source,
+                state: state.clone(),
+                gain: 1.,
+            },
+            state,
+        )
+    }
+
+    fn finish(&self) -> Option<Sample> {
+        self.state.finished.store(true);
+        None
+    }
+}
+
+impl Sampler for Voice {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        if self.state.is_fading() {
+            let frames = self.state.fade_out.load() * frame.sample_rate as f32;
+            self.gain -= 1. / frames.max(1.);
+            if self.gain <= 0. {
+                return self.finish();
+            }
+        }
+
+        let sample = match self.source.sample(frame) {
+            Some(sample) => sample * self.gain,
+            None => return self.finish(),
+        };
+
+        let peak = sample.left.abs().max(sample.right.abs());
+        let decay = (-1. / (LEVEL_DECAY.as_secs_f32() * frame.sample_rate as f32)).exp();
+        self.state
+            .level
+            .store(peak.max(self.state.level.load() * decay));
+
+        Some(sample)
+    }
+}
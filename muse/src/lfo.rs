@@ -0,0 +1,179 @@
+use crate::{parameter::Parameter, sampler::FrameInfo};
+use std::{f32::consts::PI, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub enum LfoWaveform {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    Sawtooth,
+}
+
+impl LfoWaveform {
+    /// The value at `phase`, a fraction of a cycle. Every waveform except
+    /// `Square` starts at 0 and rises.
+    pub fn value(self, phase: f32) -> f32 {
+        match self {
+            Self::Sine => (2. * PI * phase).sin(),
+            Self::Triangle => 4. * ((phase + 0.75).fract() - 0.5).abs() - 1.,
+            Self::Square => {
+                if phase < 0.5 {
+                    1.
+                } else {
+                    -1.
+                }
+            }
+            Self::Sawtooth => 2. * (phase + 0.5).fract() - 1.,
+        }
+    }
+}
+
+/// A low frequency oscillator producing `offset + depth * waveform`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LfoConfiguration {
+    pub waveform: LfoWaveform,
+    /// The frequency in hertz.
+    pub rate: f32,
+    pub depth: f32,
+    pub offset: f32,
+    /// How long to output `offset` before the LFO starts.
+    pub delay: Option<Duration>,
+    /// How long the depth takes to ramp up after the delay.
+    pub fade_in: Option<Duration>,
+    /// Restarts the cycle for each note. Otherwise, every note shares one
+    /// free-running cycle.
+    pub key_sync: bool,
+}
+
+impl LfoConfiguration {
+    pub fn new(waveform: LfoWaveform, rate: f32) -> Self {
+        Self {
+            waveform,
+            rate,
+            depth: 1.,
+            offset: 0.,
+            delay: None,
+            fade_in: None,
+            key_sync: false,
+        }
+    }
+
+    pub fn depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    pub fn fade_in(mut self, fade_in: Duration) -> Self {
+        self.fade_in = Some(fade_in);
+        self
+    }
+
+    pub fn key_sync(mut self, key_sync: bool) -> Self {
+        self.key_sync = key_sync;
+        self
+    }
+
+    pub fn as_parameter(&self) -> Parameter {
+        Parameter::Lfo(Box::new(Lfo {
+            config: self.clone(),
+            frames: 0,
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    config: LfoConfiguration,
+    /// Frames sampled since the note started.
+    frames: usize,
+}
+
+impl Lfo {
+    pub fn next(&mut self, frame: &FrameInfo) -> f32 {
+        let sample_rate = frame.sample_rate as f64;
+        let elapsed = self.frames as f64 / sample_rate;
+        self.frames += 1;
+
+        let delay = self.config.delay.unwrap_or_default().as_secs_f64();
+        if elapsed < delay {
+            return self.config.offset;
+        }
+
+        let running = elapsed - delay;
+        let fade = match self.config.fade_in {
+            Some(fade_in) if fade_in.as_secs_f64() > running => {
+                (running / fade_in.as_secs_f64()) as f32
+            }
+            _ => 1.,
+        };
+        let time = if self.config.key_sync {
+            running
+        } else {
+            frame.clock as f64 / sample_rate
+        };
+        let phase = (time * self.config.rate as f64).fract() as f32;
+
+        self.config.offset + self.config.depth * fade * self.config.waveform.value(phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Note;
+
+    fn frame(clock: usize) -> FrameInfo {
+        FrameInfo {
+            clock,
+            sample_rate: 100,
+            note: Note::default(),
+        }
+    }
+
+    #[test]
+    fn delay_fade_in_and_key_sync() {
+        let config = LfoConfiguration::new(LfoWaveform::Square, 1.)
+            .depth(2.)
+            .offset(1.)
+            .delay(Duration::from_millis(100))
+            .fade_in(Duration::from_millis(200))
+            .key_sync(true);
+        let mut lfo = config.as_parameter();
+        let values = (1000..1400)
+            .map(|clock| lfo.next(&frame(clock)).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(values[..10].iter().all(|&value| value == 1.));
+        assert_eq!(values[10], 1.);
+        assert_eq!(values[20], 2.);
+        assert_eq!(values[30], 3.);
+        assert_eq!(values[50], 3.);
+        assert_eq!(values[70], -1.);
+        assert_eq!(values[115], 3.);
+    }
+
+    #[test]
+    fn free_running_follows_the_clock() {
+        let config = LfoConfiguration::new(LfoWaveform::Sawtooth, 1.);
+        let mut first = config.as_parameter();
+        let mut second = config.as_parameter();
+        first.next(&frame(10));
+        assert_eq!(first.next(&frame(75)), second.next(&frame(75)));
+        assert_eq!(second.next(&frame(75)), Some(-0.5));
+    }
+}
@@ -3,15 +3,30 @@
 
 pub mod envelope;
 pub mod instrument;
+pub mod lfo;
 pub mod manager;
 pub mod node;
 mod note;
 pub use note::*;
 pub mod parameter;
 pub mod sampler;
+pub mod soundfont;
+#[cfg(test)]
+mod test_support;
+pub mod tuning;
+pub mod wav;
 
 pub use cpal;
 
 pub mod prelude {
-    pub use super::{cpal, envelope::*, instrument::*, note::*, parameter::*, sampler::prelude::*};
+    pub use super::{
+        cpal,
+        envelope::*,
+        instrument::*,
+        lfo::*,
+        note::*,
+        parameter::*,
+        sampler::prelude::*,
+        tuning::{EqualDivision, JustIntonation, Tuning},
+    };
 }
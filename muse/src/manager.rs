@@ -1,40 +1,168 @@
-use crate::{note::Note, sampler::PreparedSampler};
-use cpal::traits::{DeviceTrait, StreamTrait};
+use crate::{
+    note::Note,
+    sampler::{FrameInfo, PreparedSampler, Sample, Sampler},
+};
 use crossbeam::{
+    atomic::AtomicCell,
     channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender},
     sync::ShardedLock,
 };
-use std::{sync::Arc, time::Duration};
+use std::{sync::Arc, thread::JoinHandle, time::Duration};
+mod backend;
 mod cpal_thread;
 mod device;
+mod recording;
 mod sampler_thread;
-pub use device::Device;
+pub use backend::{
+    Backend, ChannelRoute, ChannelRouting, CpalBackend, FileBackend, NullBackend, OutputConfig,
+    RouteSource, RunningBackend,
+};
+pub use device::{Device, HardwareError, PlaybackError, RenderError};
+pub use recording::{Recording, RecordingError};
+
+/// The number of stereo buses each device mixes independently.
+pub const BUS_COUNT: usize = 4;
+
+/// A single frame of output containing each bus's sample.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BusFrame {
+    pub buses: [Sample; BUS_COUNT],
+}
+
+impl BusFrame {
+    /// The combined output of every bus.
+    pub fn mix(&self) -> Sample {
+        self.buses.iter().copied().sum()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlaybackOptions {
+    pub bus: usize,
+}
+
+impl PlaybackOptions {
+    pub fn bus(mut self, bus: usize) -> Self {
+        self.bus = bus;
+        self
+    }
+}
 
 pub(crate) enum ManagerMessage {
     Append {
         note: Note,
         sampler: PreparedSampler,
+        options: PlaybackOptions,
         callback: Sender<PlayingHandle>,
     },
 }
 
 pub type ManagerHandle = Arc<ShardedLock<Manager>>;
 
+/// How long pitch bends take to reach their new value, which prevents
+/// audible steps when a controller sends coarse updates.
+const PITCH_BEND_SMOOTHING: Duration = Duration::from_millis(5);
+
 #[derive(Clone, Debug)]
-pub struct PlayingHandle(Arc<u64>);
+pub struct PlayingHandle {
+    id: Arc<u64>,
+    pitch_bend: Arc<AtomicCell<f32>>,
+    /// Kept apart from `glide` so that both cells are lock-free.
+    transposition: Arc<AtomicCell<f32>>,
+    /// The seconds to slide to a new transposition.
+    glide: Arc<AtomicCell<f32>>,
+}
+
+impl PlayingHandle {
+    /// Retunes the sound by `semitones` relative to the note it was played
+    /// with.
+    pub fn set_pitch_bend(&self, semitones: f32) {
+        self.pitch_bend.store(semitones);
+    }
+
+    pub fn pitch_bend(&self) -> f32 {
+        self.pitch_bend.load()
+    }
+
+    /// Slides the sound to `semitones` relative to the note it was played
+    /// with, taking `glide` to get there. This is independent of the pitch
+    /// bend, and the two are combined.
+    pub fn transpose(&self, semitones: f32, glide: Duration) {
+        // The glide is stored first, so it is ready once the new
+        // transposition is seen.
+        self.glide.store(glide.as_secs_f32());
+        self.transposition.store(semitones);
+    }
+
+    pub fn transposition(&self) -> f32 {
+        self.transposition.load()
+    }
+}
 
 #[derive(Debug)]
 struct PlayingSound {
     note: Note,
+    bus: usize,
     handle: PlayingHandle,
+    /// The smoothed pitch bend, which is `None` until the first frame.
+    pitch_bend: Option<f32>,
+    glide: Glide,
     sampler: Arc<ShardedLock<PreparedSampler>>,
 }
 
+/// A linear slide between transpositions.
+#[derive(Debug, Default)]
+struct Glide {
+    current: f32,
+    target: f32,
+    step: f32,
+}
+
+impl Glide {
+    fn next(&mut self, handle: &PlayingHandle, sample_rate: u32) -> f32 {
+        let semitones = handle.transposition.load();
+        if semitones != self.target {
+            self.target = semitones;
+            let frames = (handle.glide.load() * sample_rate as f32).max(1.);
+            self.step = (self.target - self.current) / frames;
+        }
+
+        self.current += self.step;
+        if (self.step >= 0. && self.current >= self.target)
+            || (self.step <= 0. && self.current <= self.target)
+        {
+            self.current = self.target;
+            self.step = 0.;
+        }
+        self.current
+    }
+}
+
 impl PlayingSound {
     fn still_producing_values(&self) -> bool {
         let sampler = self.sampler.read().expect("Error reading sampler");
         sampler.still_producing_samples
     }
+
+    fn next_frame(&mut self, clock: usize, sample_rate: u32) -> FrameInfo {
+        let target = self.handle.pitch_bend();
+        let pitch_bend = match self.pitch_bend {
+            Some(current) => {
+                let smoothing =
+                    1. - (-1. / (PITCH_BEND_SMOOTHING.as_secs_f32() * sample_rate as f32)).exp();
+                current + (target - current) * smoothing
+            }
+            None => target,
+        };
+        self.pitch_bend = Some(pitch_bend);
+        let transposition = self.glide.next(&self.handle, sample_rate);
+
+        FrameInfo {
+            clock,
+            sample_rate,
+            note: self.note.bent(pitch_bend + transposition),
+        }
+    }
 }
 
 // TODO add derivative and impl debug but skip stream
@@ -42,66 +170,198 @@ pub struct Manager {
     playing_sounds: Vec<PlayingSound>,
     last_playing_sound_id: u64,
     clock: usize,
-    pub(crate) sender: Sender<ManagerMessage>,
+    sample_rate: u32,
+    taps: Vec<(u64, Sender<Sample>)>,
+    last_tap_id: u64,
+    /// Errors from the backend and recordings, returned by
+    /// [`Device::take_errors`].
+    errors: (Sender<anyhow::Error>, Receiver<anyhow::Error>),
+    /// Tells the manager and sampler threads to exit.
+    stopping: bool,
+    /// The channel to the manager thread. Offline managers have no threads, so
+    /// this is `None` and sounds are appended directly.
+    pub(crate) sender: Option<Sender<ManagerMessage>>,
 }
 
 impl Manager {
-    pub(crate) fn open_output_device(
-        output_device: cpal::Device,
-        format: cpal::StreamConfig,
-    ) -> Result<ManagerHandle, anyhow::Error> {
+    pub(crate) fn open(
+        backend: Box<dyn Backend>,
+    ) -> Result<(ManagerHandle, ManagerThreads), anyhow::Error> {
         let (sender, receiver) = unbounded();
 
         let (sample_sender, sample_receiver) = bounded(1024);
 
-        let thread_format = format.clone();
+        let sample_rate = backend.sample_rate();
 
-        let manager = Arc::new(ShardedLock::new(Manager::new(sender)));
-        // event_loop.play_stream(output_stream_id.clone())?;
+        let manager = Arc::new(ShardedLock::new(Manager::new(Some(sender), sample_rate)));
+        let errors = manager
+            .read()
+            .expect("Error reading manager")
+            .errors
+            .0
+            .clone();
 
+        // Backends may not be movable between threads once started (cpal's
+        // streams aren't), so the backend is started and kept alive on the
+        // manager thread. The result is sent back so that errors reach the caller.
+        let (started_sender, started) = bounded(1);
         let manager_for_thread = manager.clone();
-        std::thread::Builder::new()
+        let manager_thread = std::thread::Builder::new()
             .name("muse::manager".to_owned())
             .spawn(move || {
-                let callback_format = thread_format.clone();
-                let output_data_fn = move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    cpal_thread::copy_samples(&sample_receiver, data, &callback_format).unwrap();
-                    // TODO BAD UNWRAP
+                let _running = match backend.start(sample_receiver, errors) {
+                    Ok(running) => {
+                        started_sender.send(Ok(())).unwrap_or_default();
+                        running
+                    }
+                    Err(err) => {
+                        started_sender.send(Err(err)).unwrap_or_default();
+                        return;
+                    }
                 };
-                let output_stream = output_device
-                    .build_output_stream(&thread_format, output_data_fn, error_fn)
-                    .unwrap(); // TODO unwrap here is bad
 
                 ManagerThread::new(manager_for_thread, receiver)
-                    .main(output_stream)
+                    .main()
                     .unwrap_or_default()
             })?;
+        started.recv()??;
 
         let manager_for_thread = manager.clone();
-        std::thread::Builder::new()
+        let sampler_thread = std::thread::Builder::new()
             .name("muse::sampler".to_owned())
-            .spawn(move || sampler_thread::run(manager_for_thread, sample_sender, format))?;
+            .spawn(move || sampler_thread::run(manager_for_thread, sample_sender, sample_rate))?;
 
-        Ok(manager)
+        Ok((
+            manager,
+            ManagerThreads {
+                manager: manager_thread,
+                sampler: sampler_thread,
+            },
+        ))
     }
 
-    fn new(sender: Sender<ManagerMessage>) -> Self {
+    pub(crate) fn offline(sample_rate: u32) -> ManagerHandle {
+        Arc::new(ShardedLock::new(Manager::new(None, sample_rate)))
+    }
+
+    fn new(sender: Option<Sender<ManagerMessage>>, sample_rate: u32) -> Self {
         Self {
             sender,
+            sample_rate,
             playing_sounds: Vec::new(),
             last_playing_sound_id: 0,
             clock: 0,
+            taps: Vec::new(),
+            last_tap_id: 0,
+            errors: unbounded(),
+            stopping: false,
         }
     }
 
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub(crate) fn report_error(&self, error: anyhow::Error) {
+        self.errors.0.send(error).unwrap_or_default();
+    }
+
+    pub(crate) fn take_errors(&self) -> Vec<anyhow::Error> {
+        self.errors.1.try_iter().collect()
+    }
+
     fn increment_clock(&mut self) -> usize {
         self.clock = self.clock.wrapping_add(1);
         self.clock
     }
+
+    fn append(
+        &mut self,
+        note: Note,
+        sampler: PreparedSampler,
+        options: PlaybackOptions,
+    ) -> PlayingHandle {
+        self.last_playing_sound_id = self.last_playing_sound_id.wrapping_add(1);
+
+        let handle = PlayingHandle {
+            id: Arc::new(self.last_playing_sound_id),
+            pitch_bend: Arc::new(AtomicCell::new(0.)),
+            transposition: Default::default(),
+            glide: Default::default(),
+        };
+        self.playing_sounds.push(PlayingSound {
+            note,
+            bus: options.bus,
+            handle: handle.clone(),
+            pitch_bend: None,
+            glide: Glide::default(),
+            sampler: Arc::new(ShardedLock::new(sampler)),
+        });
+        handle
+    }
+
+    /// Registers a channel that receives a copy of every mixed sample.
+    fn add_tap(&mut self, tap: Sender<Sample>) -> u64 {
+        self.last_tap_id = self.last_tap_id.wrapping_add(1);
+        self.taps.push((self.last_tap_id, tap));
+        self.last_tap_id
+    }
+
+    fn remove_tap(&mut self, id: u64) {
+        self.taps.retain(|(tap_id, _)| *tap_id != id);
+    }
+
+    fn send_to_taps(&mut self, sample: Sample) {
+        if !self.taps.is_empty() {
+            self.taps.retain(|(_, tap)| tap.send(sample).is_ok());
+        }
+    }
+
+    fn release_completed_sounds(&mut self) {
+        self.playing_sounds
+            .retain(|s| s.still_producing_values() || Arc::strong_count(&s.handle.id) > 1)
+    }
+
+    /// Renders `frames` frames on the calling thread. Sounds are sampled in
+    /// the order they were played, so the output is deterministic.
+    pub(crate) fn render(&mut self, frames: usize) -> Vec<BusFrame> {
+        let mut rendered = Vec::with_capacity(frames);
+        for _ in 0..frames {
+            let clock = self.increment_clock();
+            let mut bus_frame = BusFrame::default();
+            let sample_rate = self.sample_rate;
+            for sound in self.playing_sounds.iter_mut() {
+                let frame = sound.next_frame(clock, sample_rate);
+                let mut sampler = sound.sampler.write().expect("Error locking sampler");
+                if let Some(sample) = sampler.sample(&frame) {
+                    bus_frame.buses[sound.bus] += sample;
+                }
+            }
+            self.send_to_taps(bus_frame.mix());
+            rendered.push(bus_frame);
+            self.release_completed_sounds();
+        }
+        rendered
+    }
+}
+
+/// The threads driving a device's backend.
+pub(crate) struct ManagerThreads {
+    manager: JoinHandle<()>,
+    sampler: JoinHandle<()>,
 }
 
-fn error_fn(err: cpal::StreamError) {
-    eprintln!("an error occurred on stream: {}", err);
+impl ManagerThreads {
+    /// Stops sampling and waits for the backend to shut down, which lets
+    /// backends such as [`FileBackend`] finish writing.
+    pub(crate) fn stop(self, manager: &ManagerHandle) {
+        {
+            let mut manager = manager.write().expect("Error locking manager");
+            manager.stopping = true;
+        }
+        self.sampler.join().unwrap_or_default();
+        self.manager.join().unwrap_or_default();
+    }
 }
 
 struct ManagerThread {
@@ -114,9 +374,7 @@ impl ManagerThread {
         Self { manager, receiver }
     }
 
-    fn main(&mut self, stream: cpal::Stream) -> Result<(), RecvTimeoutError> {
-        stream.play().unwrap();
-
+    fn main(&mut self) -> Result<(), RecvTimeoutError> {
         // TODO cpal now supports the concept of pausing when nothing is playing. It'd be great if we could handle that situation.
         loop {
             // Check for new messages
@@ -128,7 +386,9 @@ impl ManagerThread {
                 }
             }
 
-            self.release_completed_sounds();
+            if self.release_completed_sounds() {
+                return Ok(());
+            }
         }
     }
 
@@ -137,6 +397,7 @@ impl ManagerThread {
             Ok(ManagerMessage::Append {
                 note,
                 sampler,
+                options,
                 callback,
             }) => {
                 let handle = {
@@ -145,15 +406,7 @@ impl ManagerThread {
                         .manager
                         .write()
                         .expect("Error locking manager to add sampler");
-                    manager.last_playing_sound_id = manager.last_playing_sound_id.wrapping_add(1);
-
-                    let handle = PlayingHandle(Arc::new(manager.last_playing_sound_id));
-                    manager.playing_sounds.push(PlayingSound {
-                        note,
-                        handle: handle.clone(),
-                        sampler: Arc::new(ShardedLock::new(sampler)),
-                    });
-                    handle
+                    manager.append(note, sampler, options)
                 };
 
                 callback.send(handle).unwrap_or_default();
@@ -164,14 +417,14 @@ impl ManagerThread {
         }
     }
 
-    fn release_completed_sounds(&mut self) {
+    /// Returns true once the manager is stopping.
+    fn release_completed_sounds(&mut self) -> bool {
         let mut manager = self.manager.write().expect("Error locking manager");
-        manager
-            .playing_sounds
-            .retain(|s| s.still_producing_values() || Arc::strong_count(&s.handle.0) > 1)
+        manager.release_completed_sounds();
+        manager.stopping
     }
 }
 
 pub mod prelude {
-    pub use super::{Device, Manager, ManagerHandle};
+    pub use super::{Device, Manager, ManagerHandle, PlaybackOptions};
 }
@@ -0,0 +1,65 @@
+use crate::manager::BusFrame;
+use crossbeam::channel::{Receiver, Sender};
+use std::time::{Duration, Instant};
+
+mod file;
+mod hardware;
+mod null;
+mod routing;
+pub use file::FileBackend;
+pub use hardware::{CpalBackend, OutputConfig};
+pub use null::NullBackend;
+pub use routing::{ChannelRoute, ChannelRouting, RouteSource};
+
+/// Whatever a backend needs to keep alive while it's running, such as a cpal stream.
+pub type RunningBackend = Box<dyn std::any::Any>;
+
+/// A destination for the frames the manager produces. Each frame contains
+/// every bus, and backends that only have a single output play
+/// [`BusFrame::mix`].
+pub trait Backend: Send + 'static {
+    fn sample_rate(&self) -> u32;
+
+    /// Begins consuming `samples`. This is called on the manager's thread, and
+    /// the returned value is kept alive for as long as the manager runs. It is
+    /// dropped when the device is dropped. Errors that happen after starting
+    /// are sent to `errors`, which [`Device::take_errors`] returns.
+    ///
+    /// [`Device::take_errors`]: crate::manager::Device::take_errors
+    fn start(
+        self: Box<Self>,
+        frames: Receiver<BusFrame>,
+        errors: Sender<anyhow::Error>,
+    ) -> Result<RunningBackend, anyhow::Error>;
+}
+
+/// Pulls frames at the same pace a sound card would, so that sounds play for
+/// their real duration even when there's no hardware involved. Returns once
+/// the sampler stops sending frames.
+pub(crate) fn consume_in_realtime<E, F>(
+    frames: Receiver<BusFrame>,
+    sample_rate: u32,
+    mut consumer: F,
+) -> Result<(), E>
+where
+    F: FnMut(BusFrame) -> Result<(), E>,
+{
+    // Consume in 10ms blocks to keep the number of sleeps reasonable
+    let block_size = (sample_rate / 100).max(1) as u64;
+    let start = Instant::now();
+    let mut consumed = 0u64;
+    loop {
+        for _ in 0..block_size {
+            match frames.recv() {
+                Ok(frame) => consumer(frame)?,
+                Err(_) => return Ok(()),
+            }
+        }
+
+        consumed += block_size;
+        let target = Duration::from_secs_f64(consumed as f64 / sample_rate as f64);
+        if let Some(remaining) = target.checked_sub(start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    manager::{
+        backend::{consume_in_realtime, Backend, RunningBackend},
+        BusFrame,
+    },
+    wav::{self, WavSpec, WavWriter},
+};
+use crossbeam::channel::{Receiver, Sender};
+use std::{fs::File, io::BufWriter, path::Path, thread::JoinHandle};
+
+/// Streams the mix of everything that is played to a WAV file instead of a sound card.
+pub struct FileBackend {
+    writer: WavWriter<BufWriter<File>>,
+}
+
+impl FileBackend {
+    pub fn create<P: AsRef<Path>>(path: P, spec: WavSpec) -> Result<Self, wav::Error> {
+        Ok(Self {
+            writer: WavWriter::create(path, spec)?,
+        })
+    }
+}
+
+impl Backend for FileBackend {
+    fn sample_rate(&self) -> u32 {
+        self.writer.spec().sample_rate
+    }
+
+    fn start(
+        self: Box<Self>,
+        frames: Receiver<BusFrame>,
+        errors: Sender<anyhow::Error>,
+    ) -> Result<RunningBackend, anyhow::Error> {
+        let mut writer = self.writer;
+        let sample_rate = writer.spec().sample_rate;
+        let thread = std::thread::Builder::new()
+            .name("muse::file".to_owned())
+            .spawn(move || {
+                let mut unflushed = 0;
+                let result = consume_in_realtime(frames, sample_rate, |frame| {
+                    writer.write_sample(frame.mix())?;
+                    // Keep the header up to date in case the process exits
+                    // without dropping the device.
+                    unflushed += 1;
+                    if unflushed >= sample_rate {
+                        unflushed = 0;
+                        writer.flush()?;
+                    }
+                    Ok::<_, wav::Error>(())
+                })
+                .and_then(|_| writer.finalize());
+
+                if let Err(err) = result {
+                    errors.send(err.into()).unwrap_or_default();
+                }
+            })?;
+
+        Ok(Box::new(WriterThread(Some(thread))))
+    }
+}
+
+/// Waits for the file to be finalized when the device is dropped. The writer
+/// finishes once the sampler stops sending frames.
+struct WriterThread(Option<JoinHandle<()>>);
+
+impl Drop for WriterThread {
+    fn drop(&mut self) {
+        if let Some(thread) = self.0.take() {
+            thread.join().unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::manager::{
+    backend::{Backend, ChannelRouting, RunningBackend},
+    cpal_thread, BusFrame, HardwareError,
+};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crossbeam::channel::{Receiver, Sender};
+
+/// The output device and stream settings to request. Anything left as `None`
+/// uses the device's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    pub device: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub buffer_size: Option<u32>,
+    pub channels: Option<u16>,
+    /// How buses are sent to the output channels. Defaults to
+    /// [`ChannelRouting::stereo_mix`].
+    pub routing: Option<ChannelRouting>,
+}
+
+impl OutputConfig {
+    pub fn device<S: Into<String>>(mut self, name: S) -> Self {
+        self.device = Some(name.into());
+        self
+    }
+
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    /// The number of frames requested per callback.
+    pub fn buffer_size(mut self, frames: u32) -> Self {
+        self.buffer_size = Some(frames);
+        self
+    }
+
+    pub fn channels(mut self, channels: u16) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn routing(mut self, routing: ChannelRouting) -> Self {
+        self.routing = Some(routing);
+        self
+    }
+}
+
+/// Plays samples through an audio device using cpal.
+pub struct CpalBackend {
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    routing: ChannelRouting,
+}
+
+impl CpalBackend {
+    pub fn default_output() -> Result<Self, HardwareError> {
+        Self::open_output(&OutputConfig::default())
+    }
+
+    pub fn output_device_names() -> Result<Vec<String>, HardwareError> {
+        let host = cpal::default_host();
+        let mut names = Vec::new();
+        for device in host.output_devices()? {
+            names.push(device.name()?);
+        }
+        Ok(names)
+    }
+
+    pub fn open_output(config: &OutputConfig) -> Result<Self, HardwareError> {
+        let host = cpal::default_host();
+        let device = match &config.device {
+            Some(name) => {
+                let mut found = None;
+                for device in host.output_devices()? {
+                    if &device.name()? == name {
+                        found = Some(device);
+                        break;
+                    }
+                }
+                found.ok_or_else(|| HardwareError::DeviceNotFound(name.clone()))?
+            }
+            None => host
+                .default_output_device()
+                .ok_or(HardwareError::NoDefaultOutputDevice)?,
+        };
+
+        let (stream_config, sample_format) = Self::stream_config(&device, config)?;
+        let routing = config
+            .routing
+            .clone()
+            .unwrap_or_else(|| ChannelRouting::stereo_mix(stream_config.channels));
+        routing.validate(stream_config.channels)?;
+
+        Ok(Self {
+            device,
+            config: stream_config,
+            sample_format,
+            routing,
+        })
+    }
+
+    fn stream_config(
+        device: &cpal::Device,
+        requested: &OutputConfig,
+    ) -> Result<(cpal::StreamConfig, cpal::SampleFormat), HardwareError> {
+        let default = device.default_output_config()?;
+        if requested.sample_rate.is_none()
+            && requested.buffer_size.is_none()
+            && requested.channels.is_none()
+        {
+            return Ok((default.config(), default.sample_format()));
+        }
+
+        let sample_rate = requested.sample_rate.unwrap_or(default.sample_rate().0);
+        let channels = requested.channels.unwrap_or_else(|| default.channels());
+        let mut candidates = device
+            .supported_output_configs()?
+            .filter(|range| {
+                range.channels() == channels
+                    && range.min_sample_rate().0 <= sample_rate
+                    && range.max_sample_rate().0 >= sample_rate
+            })
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Err(match requested.channels {
+                Some(channels)
+                    if !device
+                        .supported_output_configs()?
+                        .any(|range| range.channels() == channels) =>
+                {
+                    HardwareError::UnsupportedChannelCount(channels)
+                }
+                _ => HardwareError::UnsupportedSampleRate(sample_rate),
+            });
+        }
+        // Prefer the default sample format, followed by f32 which needs no conversion.
+        candidates.sort_by_key(|range| {
+            (
+                range.sample_format() != default.sample_format(),
+                range.sample_format() != cpal::SampleFormat::F32,
+            )
+        });
+        let range = candidates.remove(0);
+
+        let buffer_size = match requested.buffer_size {
+            Some(requested) => {
+                if let cpal::SupportedBufferSize::Range { min, max } = range.buffer_size() {
+                    if requested < *min || requested > *max {
+                        return Err(HardwareError::UnsupportedBufferSize {
+                            requested,
+                            min: *min,
+                            max: *max,
+                        });
+                    }
+                }
+                cpal::BufferSize::Fixed(requested)
+            }
+            None => cpal::BufferSize::Default,
+        };
+
+        let range = range.with_sample_rate(cpal::SampleRate(sample_rate));
+        let mut config = range.config();
+        config.buffer_size = buffer_size;
+        Ok((config, range.sample_format()))
+    }
+
+    fn build_stream<T: cpal::Sample>(
+        &self,
+        frames: Receiver<BusFrame>,
+        errors: Sender<anyhow::Error>,
+    ) -> Result<cpal::Stream, cpal::BuildStreamError> {
+        let routing = self.routing.clone();
+        let mut channel_values = vec![0.; self.config.channels as usize];
+        let output_data_fn = move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            // The sampler stops before the stream while a device shuts down,
+            // so play silence rather than failing.
+            if cpal_thread::copy_samples(&frames, data, &routing, &mut channel_values).is_err() {
+                for output in data.iter_mut() {
+                    *output = cpal::Sample::from(&0f32);
+                }
+            }
+        };
+        let error_fn = move |err: cpal::StreamError| {
+            errors.send(err.into()).unwrap_or_default();
+        };
+        self.device
+            .build_output_stream(&self.config, output_data_fn, error_fn)
+    }
+}
+
+impl Backend for CpalBackend {
+    fn sample_rate(&self) -> u32 {
+        self.config.sample_rate.0
+    }
+
+    fn start(
+        self: Box<Self>,
+        frames: Receiver<BusFrame>,
+        errors: Sender<anyhow::Error>,
+    ) -> Result<RunningBackend, anyhow::Error> {
+        let stream = match self.sample_format {
+            cpal::SampleFormat::F32 => self.build_stream::<f32>(frames, errors)?,
+            cpal::SampleFormat::I16 => self.build_stream::<i16>(frames, errors)?,
+            cpal::SampleFormat::U16 => self.build_stream::<u16>(frames, errors)?,
+        };
+        stream.play()?;
+
+        Ok(Box::new(stream))
+    }
+}
@@ -0,0 +1,38 @@
+use crate::manager::{
+    backend::{consume_in_realtime, Backend, RunningBackend},
+    BusFrame,
+};
+use crossbeam::channel::{Receiver, Sender};
+
+/// Discards every frame, allowing instruments to play without any hardware.
+#[derive(Debug, Clone, Copy)]
+pub struct NullBackend {
+    sample_rate: u32,
+}
+
+impl NullBackend {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { sample_rate }
+    }
+}
+
+impl Backend for NullBackend {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn start(
+        self: Box<Self>,
+        frames: Receiver<BusFrame>,
+        _errors: Sender<anyhow::Error>,
+    ) -> Result<RunningBackend, anyhow::Error> {
+        let sample_rate = self.sample_rate;
+        std::thread::Builder::new()
+            .name("muse::null".to_owned())
+            .spawn(move || {
+                consume_in_realtime(frames, sample_rate, |_| Ok::<_, ()>(())).unwrap_or_default()
+            })?;
+
+        Ok(Box::new(()))
+    }
+}
@@ -0,0 +1,128 @@
+use crate::manager::{BusFrame, HardwareError, BUS_COUNT};
+
+/// What a [`ChannelRoute`] sends to its output channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteSource {
+    /// Every bus mixed together.
+    Mix,
+    Bus(usize),
+}
+
+/// Sends a stereo source to a pair of output channels. If `left` and `right`
+/// are the same channel, the source is mixed down to mono.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelRoute {
+    pub source: RouteSource,
+    pub left: u16,
+    pub right: u16,
+}
+
+/// Describes how buses are mapped onto a device's output channels. Channels
+/// without a route are silent, and channels with multiple routes sum them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelRouting {
+    pub routes: Vec<ChannelRoute>,
+}
+
+impl ChannelRouting {
+    /// Sends the full mix to the first two channels, or the only channel of a
+    /// mono device.
+    pub fn stereo_mix(channels: u16) -> Self {
+        match channels {
+            1 => Self::default().route(RouteSource::Mix, 0, 0),
+            _ => Self::default().route(RouteSource::Mix, 0, 1),
+        }
+    }
+
+    pub fn route(mut self, source: RouteSource, left: u16, right: u16) -> Self {
+        self.routes.push(ChannelRoute {
+            source,
+            left,
+            right,
+        });
+        self
+    }
+
+    pub(crate) fn validate(&self, channels: u16) -> Result<(), HardwareError> {
+        for route in &self.routes {
+            if let RouteSource::Bus(bus) = route.source {
+                if bus >= BUS_COUNT {
+                    return Err(HardwareError::InvalidBus(bus));
+                }
+            }
+
+            for &channel in &[route.left, route.right] {
+                if channel >= channels {
+                    return Err(HardwareError::InvalidChannel { channel, channels });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes one frame into `output`, which holds one value per channel.
+    pub(crate) fn write_frame(&self, frame: &BusFrame, output: &mut [f32]) {
+        output.iter_mut().for_each(|value| *value = 0.);
+        for route in &self.routes {
+            let sample = match route.source {
+                RouteSource::Mix => frame.mix(),
+                RouteSource::Bus(bus) => frame.buses[bus],
+            };
+
+            if route.left == route.right {
+                output[route.left as usize] += (sample.left + sample.right) / 2.;
+            } else {
+                output[route.left as usize] += sample.left;
+                output[route.right as usize] += sample.right;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::Sample;
+
+    fn frame() -> BusFrame {
+        let mut frame = BusFrame::default();
+        frame.buses[0] = Sample {
+            left: 0.5,
+            right: 0.25,
+        };
+        frame.buses[1] = Sample {
+            left: 0.1,
+            right: 0.2,
+        };
+        frame
+    }
+
+    #[test]
+    fn separate_buses() {
+        let routing = ChannelRouting::default()
+            .route(RouteSource::Bus(0), 0, 1)
+            .route(RouteSource::Bus(1), 2, 3)
+            .route(RouteSource::Bus(1), 4, 4);
+        let mut output = [1.; 6];
+        routing.write_frame(&frame(), &mut output);
+        assert_eq!(output, [0.5, 0.25, 0.1, 0.2, 0.15, 0.]);
+    }
+
+    #[test]
+    fn validation() {
+        assert!(ChannelRouting::stereo_mix(2).validate(2).is_ok());
+        assert!(matches!(
+            ChannelRouting::stereo_mix(2).validate(1),
+            Err(HardwareError::InvalidChannel {
+                channel: 1,
+                channels: 1
+            })
+        ));
+        assert!(matches!(
+            ChannelRouting::default()
+                .route(RouteSource::Bus(BUS_COUNT), 0, 1)
+                .validate(2),
+            Err(HardwareError::InvalidBus(_))
+        ));
+    }
+}
@@ -1,24 +1,18 @@
-use crate::sampler::Sample;
+use crate::manager::{BusFrame, ChannelRouting};
 use crossbeam::channel::Receiver;
 
-pub fn copy_samples(
-    samples: &Receiver<Sample>,
-    data: &mut [f32],
-    format: &cpal::StreamConfig,
+pub fn copy_samples<T: cpal::Sample>(
+    frames: &Receiver<BusFrame>,
+    data: &mut [T],
+    routing: &ChannelRouting,
+    channel_values: &mut [f32],
 ) -> Result<(), anyhow::Error> {
-    for sample in data.chunks_mut(format.channels as usize) {
-        let generated_sample = samples.recv()?;
+    for output in data.chunks_mut(channel_values.len()) {
+        let frame = frames.recv()?;
+        routing.write_frame(&frame, channel_values);
 
-        match format.channels {
-            1 => {
-                sample[0] =
-                    cpal::Sample::from(&((generated_sample.left + generated_sample.right) / 2.0))
-            }
-            2 => {
-                sample[0] = cpal::Sample::from(&generated_sample.left);
-                sample[1] = cpal::Sample::from(&generated_sample.right);
-            }
-            _ => panic!("Unsupported number of channels {}", format.channels),
+        for (output, value) in output.iter_mut().zip(channel_values.iter()) {
+            *output = cpal::Sample::from(value);
         }
     }
 
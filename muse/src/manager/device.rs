@@ -1,10 +1,14 @@
 use crate::{
-    manager::{Manager, ManagerHandle, ManagerMessage, PlayingHandle},
+    manager::{
+        Backend, BusFrame, CpalBackend, Manager, ManagerHandle, ManagerMessage, ManagerThreads,
+        NullBackend, OutputConfig, PlaybackOptions, PlayingHandle, Recording, BUS_COUNT,
+    },
     note::Note,
-    sampler::PreparedSampler,
+    sampler::{PreparedSampler, Sample},
+    wav::{Channels, WavFormat, WavSpec},
 };
-use cpal::traits::{DeviceTrait, HostTrait};
 use crossbeam::channel::bounded;
+use std::{path::Path, time::Duration};
 
 #[derive(thiserror::Error, Debug)]
 pub enum HardwareError {
@@ -14,43 +18,348 @@ pub enum HardwareError {
     DevicesError(#[from] cpal::DevicesError),
     #[error("Error getting device name {0}")]
     DeviceNameError(#[from] cpal::DeviceNameError),
-    // #[error("Error getting supported formats {0}")]
-    // SupportedFormatsError(#[from] cpal::SupportedFormatsError),
-    // #[error("Error getting default format {0}")]
-    // DefaultFormatError(#[from] cpal::DefaultFormatError),
+    #[error("Error getting supported formats {0}")]
+    SupportedFormatsError(#[from] cpal::SupportedStreamConfigsError),
+    #[error("Error getting default format {0}")]
+    DefaultFormatError(#[from] cpal::DefaultStreamConfigError),
+    #[error("no output device named {0:?}")]
+    DeviceNotFound(String),
+    #[error("the output device doesn't support a sample rate of {0}")]
+    UnsupportedSampleRate(u32),
+    #[error("the output device supports buffer sizes from {min} to {max}, but {requested} was requested")]
+    UnsupportedBufferSize { requested: u32, min: u32, max: u32 },
+    #[error("the output device doesn't support {0} channels")]
+    UnsupportedChannelCount(u16),
+    #[error("channel {channel} was routed, but the device only has {channels} channels")]
+    InvalidChannel { channel: u16, channels: u16 },
+    #[error("bus {0} doesn't exist")]
+    InvalidBus(usize),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum PlaybackError {
+    #[error("bus {0} doesn't exist")]
+    InvalidBus(usize),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RenderError {
+    #[error("only offline devices can be rendered")]
+    NotOffline,
+}
+
+/// An audio output. Dropping a device stops its threads and shuts down its
+/// backend.
 pub struct Device {
     manager: ManagerHandle,
+    /// Offline devices have no threads.
+    threads: Option<ManagerThreads>,
 }
 
 impl Device {
     pub fn default_output() -> Result<Self, anyhow::Error> {
-        let host = cpal::default_host();
-        if let Some(cpal_device) = host.default_output_device() {
-            let format = cpal_device.default_output_config()?;
-            let manager = Manager::open_output_device(cpal_device, format.into())?;
-            Ok(Self { manager })
-        } else {
-            Err(anyhow::Error::from(HardwareError::NoDefaultOutputDevice))
+        Self::with_backend(CpalBackend::default_output()?)
+    }
+
+    /// Opens an output device, falling back to the default device and stream
+    /// settings for anything not specified in `config`.
+    pub fn open_output(config: &OutputConfig) -> Result<Self, anyhow::Error> {
+        Self::with_backend(CpalBackend::open_output(config)?)
+    }
+
+    pub fn output_device_names() -> Result<Vec<String>, HardwareError> {
+        CpalBackend::output_device_names()
+    }
+
+    pub fn with_backend<B: Backend>(backend: B) -> Result<Self, anyhow::Error> {
+        let (manager, threads) = Manager::open(Box::new(backend))?;
+        Ok(Self {
+            manager,
+            threads: Some(threads),
+        })
+    }
+
+    /// Creates a device that plays in realtime but discards its output.
+    pub fn null(sample_rate: u32) -> Result<Self, anyhow::Error> {
+        Self::with_backend(NullBackend::new(sample_rate))
+    }
+
+    /// Creates a device that isn't connected to any hardware. Nothing is
+    /// sampled until [`Device::render`] is called.
+    pub fn offline(sample_rate: u32) -> Self {
+        Self {
+            manager: Manager::offline(sample_rate),
+            threads: None,
         }
     }
 
+    pub fn sample_rate(&self) -> u32 {
+        let manager = self.manager.read().expect("Error reading manager");
+        manager.sample_rate()
+    }
+
     pub fn play(
         &self,
         sampler: PreparedSampler,
         note: Note,
     ) -> Result<PlayingHandle, anyhow::Error> {
-        let (callback, handle) = bounded(1);
-        {
+        self.play_with_options(sampler, note, PlaybackOptions::default())
+    }
+
+    pub fn play_with_options(
+        &self,
+        sampler: PreparedSampler,
+        note: Note,
+        options: PlaybackOptions,
+    ) -> Result<PlayingHandle, anyhow::Error> {
+        if options.bus >= BUS_COUNT {
+            return Err(PlaybackError::InvalidBus(options.bus).into());
+        }
+
+        let sender = {
             let manager = self.manager.read().expect("Error reading manager");
-            manager.sender.send(ManagerMessage::Append {
-                note,
-                sampler,
-                callback,
-            })?;
+            manager.sender.clone()
+        };
+
+        match sender {
+            Some(sender) => {
+                let (callback, handle) = bounded(1);
+                sender.send(ManagerMessage::Append {
+                    note,
+                    sampler,
+                    options,
+                    callback,
+                })?;
+
+                Ok(handle.recv()?)
+            }
+            None => {
+                let mut manager = self.manager.write().expect("Error locking manager");
+                Ok(manager.append(note, sampler, options))
+            }
+        }
+    }
+
+    /// Starts writing the device's mixed output to a WAV file. Playback is
+    /// unaffected, and recording stops when the returned [`Recording`] is
+    /// finished or dropped.
+    pub fn record<P: AsRef<Path>>(
+        &self,
+        path: P,
+        channels: Channels,
+        format: WavFormat,
+    ) -> Result<Recording, anyhow::Error> {
+        let spec = WavSpec::new(self.sample_rate(), channels, format);
+        Recording::start(self.manager.clone(), path, spec)
+    }
+
+    /// Returns the errors that have happened in the background since the last
+    /// call, such as stream errors or recordings that failed to finish.
+    pub fn take_errors(&self) -> Vec<anyhow::Error> {
+        let manager = self.manager.read().expect("Error reading manager");
+        manager.take_errors()
+    }
+
+    /// Renders the next `frames` samples of an offline device as fast as possible.
+    pub fn render(&self, frames: usize) -> Result<Vec<Sample>, RenderError> {
+        Ok(self
+            .render_buses(frames)?
+            .iter()
+            .map(BusFrame::mix)
+            .collect())
+    }
+
+    /// Renders the next `frames` frames of an offline device, keeping each
+    /// bus separate.
+    pub fn render_buses(&self, frames: usize) -> Result<Vec<BusFrame>, RenderError> {
+        let mut manager = self.manager.write().expect("Error locking manager");
+        if manager.sender.is_some() {
+            return Err(RenderError::NotOffline);
+        }
+
+        Ok(manager.render(frames))
+    }
+
+    pub fn render_duration(&self, duration: Duration) -> Result<Vec<Sample>, RenderError> {
+        let frames = (duration.as_secs_f64() * self.sample_rate() as f64).round() as usize;
+        self.render(frames)
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        if let Some(threads) = self.threads.take() {
+            threads.stop(&self.manager);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        manager::FileBackend,
+        parameter::Parameter,
+        sampler::{FrameInfo, Oscillator, PreparableSampler, Sampler, Sine},
+        test_support::TempDir,
+    };
+    use crossbeam::channel::Sender;
+
+    #[derive(Debug)]
+    struct Countdown(usize);
+
+    impl Sampler for Countdown {
+        fn sample(&mut self, _frame: &FrameInfo) -> Option<Sample> {
+            self.0 = self.0.checked_sub(1)?;
+            Some(Sample::default())
+        }
+    }
+
+    /// Plays `remaining` frames at full volume, then reports that it has
+    /// finished.
+    #[derive(Debug)]
+    struct Signal {
+        remaining: usize,
+        finished: Sender<()>,
+    }
+
+    impl Sampler for Signal {
+        fn sample(&mut self, _frame: &FrameInfo) -> Option<Sample> {
+            self.remaining = self.remaining.checked_sub(1)?;
+            if self.remaining == 0 {
+                self.finished.send(()).unwrap_or_default();
+            }
+            Some(Sample {
+                left: 1.,
+                right: 1.,
+            })
         }
+    }
+
+    fn playing_sounds(device: &Device) -> usize {
+        device.manager.read().unwrap().playing_sounds.len()
+    }
+
+    #[test]
+    fn offline_render() {
+        let device = Device::offline(44_100);
+        let _handle = device
+            .play(
+                Oscillator::<Sine>::new(Parameter::Value(441.), Parameter::Value(1.)).prepare(),
+                Note::default(),
+            )
+            .unwrap();
+
+        let samples = device.render_duration(Duration::from_millis(10)).unwrap();
+        assert_eq!(samples.len(), 441);
+        // The oscillator splits its amplitude between both channels
+        let peak = samples.iter().map(|s| s.left).fold(0f32, f32::max);
+        assert!((peak - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn pitch_bend_retunes_playing_sounds() {
+        let device = Device::offline(44_100);
+        let handle = device
+            .play(
+                Oscillator::<Sine>::new(Parameter::NoteHertz, Parameter::Value(1.)).prepare(),
+                Note::from_hertz(441., 127),
+            )
+            .unwrap();
+        let cycles = |samples: Vec<Sample>| {
+            samples
+                .windows(2)
+                .filter(|pair| pair[0].left < 0. && pair[1].left >= 0.)
+                .count()
+        };
+
+        // Bends set before the first frame apply immediately
+        handle.set_pitch_bend(-12.);
+        let samples = device.render_duration(Duration::from_millis(100)).unwrap();
+        assert_eq!(cycles(samples), 22);
+
+        // Later bends are smoothed
+        handle.set_pitch_bend(12.);
+        let samples = device.render_duration(Duration::from_millis(5)).unwrap();
+        assert!(cycles(samples) < 4);
+        device.render_duration(Duration::from_millis(50)).unwrap();
+        let samples = device.render_duration(Duration::from_millis(100)).unwrap();
+        assert!((87..=89).contains(&cycles(samples)));
+    }
+
+    #[test]
+    fn separate_buses() {
+        let device = Device::offline(44_100);
+        let _handle = device
+            .play_with_options(
+                Oscillator::<Sine>::new(Parameter::Value(441.), Parameter::Value(1.)).prepare(),
+                Note::default(),
+                PlaybackOptions::default().bus(2),
+            )
+            .unwrap();
+        assert!(device
+            .play_with_options(
+                Countdown(1).prepare(),
+                Note::default(),
+                PlaybackOptions::default().bus(BUS_COUNT)
+            )
+            .is_err());
+
+        let frames = device.render(441).unwrap();
+        assert!(frames.iter().any(|sample| sample.left > 0.));
+        let frames = device.render_buses(441).unwrap();
+        assert!(frames.iter().all(|frame| frame.buses[0].left == 0.));
+        assert!(frames.iter().any(|frame| frame.buses[2].left > 0.));
+    }
+
+    #[test]
+    fn releases_completed_sounds() {
+        let device = Device::offline(44_100);
+        let handle = device
+            .play(Countdown(100).prepare(), Note::default())
+            .unwrap();
+
+        // The sound has finished, but it is kept while a handle refers to it
+        device.render(200).unwrap();
+        assert_eq!(playing_sounds(&device), 1);
+
+        drop(handle);
+        device.render(1).unwrap();
+        assert_eq!(playing_sounds(&device), 0);
+    }
+
+    #[test]
+    fn dropping_a_device_finishes_its_file() {
+        let dir = TempDir::new("device");
+        let path = dir.join("output.wav");
+        let device = Device::with_backend(
+            FileBackend::create(
+                &path,
+                WavSpec::new(44_100, Channels::Mono, WavFormat::Float32),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let (finished, sampled) = bounded(1);
+        let _handle = device
+            .play(
+                Signal {
+                    remaining: 100,
+                    finished,
+                }
+                .prepare(),
+                Note::default(),
+            )
+            .unwrap();
+        // Every frame sampled before the device is dropped is written
+        sampled.recv_timeout(Duration::from_secs(5)).unwrap();
 
-        Ok(handle.recv()?)
+        drop(device);
+        let written = hound::WavReader::open(&path)
+            .unwrap()
+            .into_samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(written.iter().filter(|&&sample| sample == 1.).count(), 100);
     }
 }
@@ -0,0 +1,100 @@
+use crate::{
+    manager::ManagerHandle,
+    sampler::Sample,
+    wav::{self, WavSpec, WavWriter},
+};
+use crossbeam::channel::{unbounded, Receiver};
+use std::{path::Path, thread::JoinHandle};
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecordingError {
+    #[error("Error writing recording {0}")]
+    Wav(#[from] wav::Error),
+    #[error("the recording thread panicked")]
+    WriterPanicked,
+}
+
+/// Writes everything a device plays to a WAV file until it is finished or
+/// dropped. Use [`Recording::finish`] to handle errors, otherwise they are
+/// reported by [`Device::take_errors`](crate::manager::Device::take_errors).
+pub struct Recording {
+    manager: ManagerHandle,
+    tap: u64,
+    writer_thread: Option<JoinHandle<Result<(), wav::Error>>>,
+}
+
+impl Recording {
+    pub(crate) fn start<P: AsRef<Path>>(
+        manager: ManagerHandle,
+        path: P,
+        spec: WavSpec,
+    ) -> Result<Self, anyhow::Error> {
+        let writer = WavWriter::create(path, spec)?;
+        let (sender, receiver) = unbounded();
+        let writer_thread = std::thread::Builder::new()
+            .name("muse::recording".to_owned())
+            .spawn(move || write_samples(writer, receiver))?;
+
+        let tap = {
+            let mut manager = manager.write().expect("Error locking manager");
+            manager.add_tap(sender)
+        };
+
+        Ok(Self {
+            manager,
+            tap,
+            writer_thread: Some(writer_thread),
+        })
+    }
+
+    /// Stops recording and waits for all samples to be written.
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<(), RecordingError> {
+        {
+            let mut manager = self.manager.write().expect("Error locking manager");
+            manager.remove_tap(self.tap);
+        }
+
+        match self.writer_thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| RecordingError::WriterPanicked)?
+                .map_err(RecordingError::from),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            let manager = self.manager.read().expect("Error reading manager");
+            manager.report_error(err.into());
+        }
+    }
+}
+
+fn write_samples<W>(mut writer: WavWriter<W>, samples: Receiver<Sample>) -> Result<(), wav::Error>
+where
+    W: std::io::Write + std::io::Seek,
+{
+    let flush_interval = writer.spec().sample_rate as usize;
+    let mut unflushed = 0;
+    // The sender is removed from the manager when the recording stops, which ends this loop.
+    while let Ok(sample) = samples.recv() {
+        writer.write_sample(sample)?;
+
+        // Periodically update the header so that the file is usable even if
+        // the process exits without finishing the recording.
+        unflushed += 1;
+        if unflushed >= flush_interval {
+            writer.flush()?;
+            unflushed = 0;
+        }
+    }
+
+    writer.finalize()
+}
@@ -1,4 +1,4 @@
-use super::ManagerHandle;
+use super::{BusFrame, ManagerHandle};
 use crate::sampler::{FrameInfo, PreparedSampler, Sample, Sampler};
 use crossbeam::{
     channel::{unbounded, Receiver, Sender},
@@ -12,42 +12,52 @@ fn desired_threads() -> usize {
     // For machines in the sweet spot of 4-8 cores, we'll use half the number of CPUs
     // Any machines with more than 8 CPUs will just use 4 threads.
     // TODO Should this be configurable?
-    (num_cpus::get() / 2).max(2).min(4)
+    (num_cpus::get() / 2).clamp(2, 4)
 }
 
-pub fn run(manager: ManagerHandle, sender: Sender<Sample>, format: cpal::StreamConfig) {
+pub fn run(manager: ManagerHandle, sender: Sender<BusFrame>, sample_rate: u32) {
     let (sample_sender, sample_receiver) = unbounded();
     let (result_sender, result_receiver) = unbounded();
 
     let thread_count = desired_threads();
-    (0..thread_count).for_each(|_| {
-        let sample_receiver = sample_receiver.clone();
-        let result_sender = result_sender.clone();
-        std::thread::spawn(|| sampler_thread_main(sample_receiver, result_sender));
-    });
+    let workers = (0..thread_count)
+        .map(|_| {
+            let sample_receiver = sample_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::spawn(|| sampler_thread_main(sample_receiver, result_sender))
+        })
+        .collect::<Vec<_>>();
     let mut thread = SamplerThread {
         manager,
         sender,
-        format,
+        sample_rate,
         sample_sender,
         result_receiver,
     };
 
     thread.run();
+
+    // Dropping the job sender ends the workers
+    drop(thread);
+    for worker in workers {
+        worker.join().unwrap_or_default();
+    }
 }
 
+/// A sound to sample, along with the bus its output is mixed into.
+type SampleJob = (usize, FrameInfo, Arc<ShardedLock<PreparedSampler>>);
+
 struct SamplerThread {
-    sample_sender: Sender<(FrameInfo, Arc<ShardedLock<PreparedSampler>>)>,
-    result_receiver: Receiver<Option<Sample>>,
+    sample_sender: Sender<SampleJob>,
+    result_receiver: Receiver<(usize, Option<Sample>)>,
     manager: ManagerHandle,
-    sender: Sender<Sample>,
-    format: cpal::StreamConfig,
+    sender: Sender<BusFrame>,
+    sample_rate: u32,
 }
 
 impl SamplerThread {
     fn run(&mut self) {
-        loop {
-            let sample = self.next_sample();
+        while let Some(sample) = self.next_sample() {
             if let Err(err) = self.sender.send(sample) {
                 println!("Error on sampler thread: {}", err);
                 break;
@@ -55,38 +65,43 @@ impl SamplerThread {
         }
     }
 
-    fn next_sample(&mut self) -> Sample {
+    /// Samples the next frame, or returns `None` once the manager is stopping.
+    fn next_sample(&mut self) -> Option<BusFrame> {
         let mut manager = self
             .manager
             .write()
             .expect("Error locking manager for sampling");
+        if manager.stopping {
+            return None;
+        }
+
         let clock = manager.increment_clock();
-        for sound in manager.playing_sounds.iter() {
-            let frame = FrameInfo {
-                clock,
-                sample_rate: self.format.sample_rate.0,
-                note: sound.note,
-            };
-            let _ = self.sample_sender.send((frame, sound.sampler.clone()));
+        for sound in manager.playing_sounds.iter_mut() {
+            let frame = sound.next_frame(clock, self.sample_rate);
+            let _ = self
+                .sample_sender
+                .send((sound.bus, frame, sound.sampler.clone()));
         }
 
-        (0..manager.playing_sounds.len())
-            .filter_map(|_| self.result_receiver.recv().ok().flatten())
-            .sum()
+        let mut bus_frame = BusFrame::default();
+        for _ in 0..manager.playing_sounds.len() {
+            if let Ok((bus, Some(sample))) = self.result_receiver.recv() {
+                bus_frame.buses[bus] += sample;
+            }
+        }
+        manager.send_to_taps(bus_frame.mix());
+        Some(bus_frame)
     }
 }
 
-fn sampler_thread_main(
-    samplers: Receiver<(FrameInfo, Arc<ShardedLock<PreparedSampler>>)>,
-    results: Sender<Option<Sample>>,
-) {
-    while let Ok((frame, sampler)) = samplers.recv() {
+fn sampler_thread_main(samplers: Receiver<SampleJob>, results: Sender<(usize, Option<Sample>)>) {
+    while let Ok((bus, frame, sampler)) = samplers.recv() {
         let sample = {
             let mut sampler = sampler.write().expect("Error locking sampler");
             sampler.sample(&frame)
         };
 
-        if results.send(sample).is_err() {
+        if results.send((bus, sample)).is_err() {
             break;
         }
     }
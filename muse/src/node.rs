@@ -1,24 +1,44 @@
 use crate::{
     envelope::{EnvelopeBuilder, EnvelopeConfiguration, EnvelopeCurve},
     instrument::{
-        serialization::{self, OscillatorFunction},
+        serialization::{self, FilterDesign, OscillatorFunction, RoundRobin},
         ControlHandles,
     },
+    lfo::LfoConfiguration,
     note::Note,
     parameter,
     prelude::ToneGenerator,
     sampler::{
-        Add, Amplify, Multiply, Oscillator, Pan, PreparableSampler, PreparedSampler, Sawtooth,
-        Sine, Square, Triangle, Unison,
+        Add, Amplify, BandLimitedSawtooth, BandLimitedSquare, BandLimitedTriangle, Biquad,
+        FilterMode, LadderFilter, LoopPoints, Multiply, Noise, NoiseColor, Oscillator, Pan,
+        PreparableSampler, PreparedSampler, Pulse, SamplePlayer, Sawtooth, Sine, Square,
+        StartPhase, StateVariableFilter, Triangle, Unison, Wavetable, WavetableData,
+    },
+    wav::WavData,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    ops::RangeInclusive,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
     },
 };
-use std::{collections::HashMap, convert::TryFrom};
 
 #[derive(Debug, Clone)]
 pub struct LoadedInstrument<T = ()> {
     output: Node<T>,
 }
 
+impl<T> LoadedInstrument<T> {
+    /// Creates an instrument that plays `output` for every note.
+    pub fn new(output: Node<T>) -> Self {
+        Self { output }
+    }
+}
+
 impl<T> Instantiatable for LoadedInstrument<T>
 where
     T: Instantiatable + Clone + std::fmt::Debug + 'static,
@@ -44,6 +64,7 @@ impl<T> LoadedInstrument<T> {
                         decay: EnvelopeCurve::from_serialization(&env.decay)?,
                         sustain: EnvelopeCurve::from_serialization(&env.sustain)?,
                         release: EnvelopeCurve::from_serialization(&env.release)?,
+                        scaling: env.scaling,
                     }
                     .build()?,
                 ))
@@ -73,28 +94,90 @@ where
     fn try_from(
         instrument_spec: serialization::Instrument<T>,
     ) -> Result<LoadedInstrument<T>, Self::Error> {
+        Self::load(instrument_spec, ".")
+    }
+}
+
+#[cfg(feature = "serialization")]
+impl<T> LoadedInstrument<T>
+where
+    T: serialization::NodeInstantiator<T> + std::fmt::Debug,
+{
+    /// Reads an instrument from a RON file. Files referenced by the instrument
+    /// are resolved relative to the directory containing `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, serialization::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let instrument_spec = ron::from_str::<serialization::Instrument<T>>(&contents)?;
+        Self::load(
+            instrument_spec,
+            path.parent().unwrap_or_else(|| Path::new(".")),
+        )
+    }
+
+    /// Reads an instrument from an SFZ file. See [`serialization::sfz`] for
+    /// the supported subset of the format.
+    pub fn open_sfz<P: AsRef<Path>>(path: P) -> Result<Self, serialization::Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let instrument_spec = serialization::sfz::parse(&contents, &name)?;
+        Self::load(
+            instrument_spec,
+            path.parent().unwrap_or_else(|| Path::new(".")),
+        )
+    }
+
+    /// Loads an instrument, resolving file paths relative to `base_path`.
+    ///
+    /// Nodes may refer to nodes declared after them, but any other error,
+    /// such as a file that can't be read, stops loading immediately.
+    pub fn load<P: AsRef<Path>>(
+        instrument_spec: serialization::Instrument<T>,
+        base_path: P,
+    ) -> Result<Self, serialization::Error> {
         use serialization::NodeInstantiator;
 
         let envelopes = Self::instantiate_envelopes(&instrument_spec.envelopes)?;
 
-        let mut context = serialization::Context::new(&envelopes);
+        let mut context = serialization::Context::new(&envelopes, base_path.as_ref());
 
         let mut nodes_to_load = instrument_spec.nodes.iter().collect::<Vec<_>>();
         while !nodes_to_load.is_empty() {
             let initial_len = nodes_to_load.len();
-            nodes_to_load.retain(|(name, node)| {
-                if let Ok(sampler) = node.instantiate_node(&mut context) {
+            let mut load_error = None;
+            nodes_to_load.retain(|(name, node)| match node.instantiate_node(&mut context) {
+                Ok(sampler) => {
                     context.node_instantiated(name, sampler);
                     // Handled, so we return false to free it
                     false
-                } else {
+                }
+                Err(err) => {
+                    // Missing nodes may be loaded later in this pass, but
+                    // anything else won't be fixed by trying again.
+                    if !matches!(
+                        err.downcast_ref::<serialization::Error>(),
+                        Some(serialization::Error::NodeNotFound(_))
+                    ) {
+                        load_error.get_or_insert(err);
+                    }
                     true
                 }
             });
 
+            if let Some(err) = load_error {
+                return Err(err
+                    .downcast::<serialization::Error>()
+                    .unwrap_or_else(serialization::Error::from));
+            }
+
             if initial_len == nodes_to_load.len() {
-                println!("{:#?}", nodes_to_load);
-                println!("{:#?}", context);
                 return Err(serialization::Error::RecursiveNodeDependencies(
                     nodes_to_load.iter().map(|n| n.0.clone()).collect(),
                 ));
@@ -123,12 +206,63 @@ pub enum Node<T> {
         function: OscillatorFunction,
         frequency: Parameter,
         amplitude: Parameter,
+        start_phase: StartPhase,
+    },
+    Pulse {
+        frequency: Parameter,
+        amplitude: Parameter,
+        width: Parameter,
+        start_phase: StartPhase,
+    },
+    Noise {
+        color: NoiseColor,
+        amplitude: Parameter,
+        /// Each instance is seeded from this and the number of instances
+        /// created before it, so notes and unison voices differ while
+        /// staying reproducible.
+        seed: Option<u64>,
+        instances: Arc<AtomicU64>,
+    },
+    Sample {
+        data: Arc<WavData>,
+        root_key: u8,
+        amplitude: Parameter,
+        loop_points: Option<LoopPoints>,
+        loop_through_release: bool,
+        one_shot: bool,
+        pitch_tracking: bool,
+        /// An offset in cents.
+        tune: f32,
+    },
+    Zoned {
+        zones: Vec<Zone<T>>,
+        round_robins: RoundRobinCounters,
+    },
+    Wavetable {
+        table: Arc<WavetableData>,
+        frequency: Parameter,
+        amplitude: Parameter,
+        morph: Parameter,
+        start_phase: StartPhase,
     },
     Unison {
         template: Box<Self>,
         quantity: u8,
         detune: Parameter,
     },
+    Filter {
+        design: FilterDesign,
+        mode: FilterMode,
+        cutoff: Parameter,
+        resonance: Parameter,
+        input: Box<Self>,
+    },
+    Ladder {
+        cutoff: Parameter,
+        resonance: Parameter,
+        drive: Parameter,
+        input: Box<Self>,
+    },
     Amplify {
         value: Parameter,
         input: Box<Self>,
@@ -156,25 +290,158 @@ where
                 frequency,
                 function,
                 amplitude,
+                start_phase,
             } => {
-                let frequency = frequency.instantiate(controls);
-                let amplitude = amplitude.instantiate(controls);
+                let frequency = frequency.instantiate(note, controls);
+                let amplitude = amplitude.instantiate(note, controls);
 
                 match function {
-                    OscillatorFunction::Sine => {
-                        Oscillator::<Sine>::new(frequency, amplitude).prepare()
-                    }
+                    OscillatorFunction::Sine => Oscillator::<Sine>::new(frequency, amplitude)
+                        .with_start_phase(*start_phase)
+                        .prepare(),
                     OscillatorFunction::Sawtooth => {
-                        Oscillator::<Sawtooth>::new(frequency, amplitude).prepare()
-                    }
-                    OscillatorFunction::Square => {
-                        Oscillator::<Square>::new(frequency, amplitude).prepare()
+                        Oscillator::<Sawtooth>::new(frequency, amplitude)
+                            .with_start_phase(*start_phase)
+                            .prepare()
                     }
+                    OscillatorFunction::Square => Oscillator::<Square>::new(frequency, amplitude)
+                        .with_start_phase(*start_phase)
+                        .prepare(),
                     OscillatorFunction::Triangle => {
-                        Oscillator::<Triangle>::new(frequency, amplitude).prepare()
+                        Oscillator::<Triangle>::new(frequency, amplitude)
+                            .with_start_phase(*start_phase)
+                            .prepare()
+                    }
+                    OscillatorFunction::BandLimitedSawtooth => {
+                        Oscillator::<BandLimitedSawtooth>::new(frequency, amplitude)
+                            .with_start_phase(*start_phase)
+                            .prepare()
+                    }
+                    OscillatorFunction::BandLimitedSquare => {
+                        Oscillator::<BandLimitedSquare>::new(frequency, amplitude)
+                            .with_start_phase(*start_phase)
+                            .prepare()
+                    }
+                    OscillatorFunction::BandLimitedTriangle => {
+                        Oscillator::<BandLimitedTriangle>::new(frequency, amplitude)
+                            .with_start_phase(*start_phase)
+                            .prepare()
+                    }
+                }
+            }
+            Node::Pulse {
+                frequency,
+                amplitude,
+                width,
+                start_phase,
+            } => Pulse::new(
+                frequency.instantiate(note, controls),
+                amplitude.instantiate(note, controls),
+                width.instantiate(note, controls),
+            )
+            .with_start_phase(*start_phase)
+            .prepare(),
+            Node::Noise {
+                color,
+                amplitude,
+                seed,
+                instances,
+            } => {
+                let seed =
+                    seed.map(|seed| seed.wrapping_add(instances.fetch_add(1, Ordering::Relaxed)));
+                Noise::new(*color, amplitude.instantiate(note, controls), seed).prepare()
+            }
+            Node::Sample {
+                data,
+                root_key,
+                amplitude,
+                loop_points,
+                loop_through_release,
+                one_shot,
+                pitch_tracking,
+                tune,
+            } => {
+                // One-shot samples ignore note-off, so their envelopes are
+                // kept apart from the note's control handles.
+                let amplitude = if *one_shot {
+                    amplitude.instantiate(note, &ControlHandles::with_bus(controls.bus().clone()))
+                } else {
+                    amplitude.instantiate(note, controls)
+                };
+                SamplePlayer::new(data.clone(), amplitude, controls.new_handle())
+                    .with_root_key(*root_key)
+                    .with_loop(*loop_points)
+                    .loop_through_release(*loop_through_release)
+                    .one_shot(*one_shot)
+                    .pitch_tracking(*pitch_tracking)
+                    .with_tuning(*tune)
+                    .prepare()
+            }
+            Node::Zoned {
+                zones,
+                round_robins,
+            } => {
+                let key = note.step().round().clamp(0., 127.) as u8;
+                let note_numbers = round_robins.advance(zones, key, note.velocity());
+                let samplers = zones
+                    .iter()
+                    .filter(|zone| {
+                        let note_number = round_robins
+                            .group_of(zone)
+                            .and_then(|group| note_numbers[group]);
+                        zone.matches(key, note.velocity(), note_number)
+                    })
+                    .map(|zone| zone.node.instantiate(note, controls))
+                    .collect::<Vec<_>>();
+                if samplers.len() == 1 {
+                    samplers.into_iter().next().unwrap()
+                } else {
+                    Add::new(samplers).prepare()
+                }
+            }
+            Node::Wavetable {
+                table,
+                frequency,
+                amplitude,
+                morph,
+                start_phase,
+            } => Wavetable::new(
+                table.clone(),
+                frequency.instantiate(note, controls),
+                amplitude.instantiate(note, controls),
+                morph.instantiate(note, controls),
+            )
+            .with_start_phase(*start_phase)
+            .prepare(),
+            Node::Filter {
+                design,
+                mode,
+                cutoff,
+                resonance,
+                input,
+            } => {
+                let cutoff = cutoff.instantiate(note, controls);
+                let resonance = resonance.instantiate(note, controls);
+                let input = input.instantiate(note, controls);
+                match design {
+                    FilterDesign::StateVariable => {
+                        StateVariableFilter::new(*mode, cutoff, resonance, input).prepare()
                     }
+                    FilterDesign::Biquad => Biquad::new(*mode, cutoff, resonance, input).prepare(),
                 }
             }
+            Node::Ladder {
+                cutoff,
+                resonance,
+                drive,
+                input,
+            } => LadderFilter::new(
+                cutoff.instantiate(note, controls),
+                resonance.instantiate(note, controls),
+                drive.instantiate(note, controls),
+                input.instantiate(note, controls),
+            )
+            .prepare(),
             Node::Multiply { inputs } => Multiply::new(
                 inputs
                     .iter()
@@ -190,12 +457,12 @@ where
             )
             .prepare(),
             Node::Amplify { value, input } => Amplify::new(
-                value.instantiate(controls),
+                value.instantiate(note, controls),
                 input.instantiate(note, controls),
             )
             .prepare(),
             Node::Pan { value, input } => Pan::new(
-                value.instantiate(controls),
+                value.instantiate(note, controls),
                 input.instantiate(note, controls),
             )
             .prepare(),
@@ -207,13 +474,120 @@ where
                 let samplers = (0..*quantity)
                     .map(|_| template.instantiate(note, controls))
                     .collect();
-                Unison::new(detune.instantiate(controls), samplers).prepare()
+                Unison::new(detune.instantiate(note, controls), samplers).prepare()
             }
             Node::Custom(custom) => custom.instantiate(note, controls),
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Zone<T> {
+    pub keys: RangeInclusive<u8>,
+    pub velocities: RangeInclusive<u8>,
+    pub round_robin: Option<RoundRobin>,
+    pub node: Node<T>,
+}
+
+impl<T> Zone<T> {
+    fn plays(&self, key: u8, velocity: u8) -> bool {
+        self.keys.contains(&key) && self.velocities.contains(&velocity)
+    }
+
+    /// `note_number` counts the notes played by this zone's round robin.
+    fn matches(&self, key: u8, velocity: u8, note_number: Option<usize>) -> bool {
+        self.plays(key, velocity)
+            && self.round_robin.is_none_or(|round_robin| {
+                note_number.is_some_and(|note_number| {
+                    note_number % round_robin.length.max(1) + 1 == round_robin.position
+                })
+            })
+    }
+}
+
+impl<T> Node<T> {
+    pub fn zoned(zones: Vec<Zone<T>>) -> Self {
+        Self::Zoned {
+            round_robins: RoundRobinCounters::new(&zones),
+            zones,
+        }
+    }
+}
+
+/// Counts the notes played by each round robin, so that each one advances
+/// only when one of its own zones could play. Zones belong to the same round
+/// robin when they have the same length and keys.
+#[derive(Debug, Default)]
+pub struct RoundRobinCounters {
+    groups: Vec<RoundRobinGroup>,
+}
+
+#[derive(Debug)]
+struct RoundRobinGroup {
+    length: usize,
+    keys: RangeInclusive<u8>,
+    notes_played: AtomicUsize,
+}
+
+impl RoundRobinCounters {
+    pub fn new<T>(zones: &[Zone<T>]) -> Self {
+        let mut counters = Self::default();
+        for zone in zones {
+            if let (Some(round_robin), None) = (zone.round_robin, counters.group_of(zone)) {
+                counters.groups.push(RoundRobinGroup {
+                    length: round_robin.length.max(1),
+                    keys: zone.keys.clone(),
+                    notes_played: AtomicUsize::new(0),
+                });
+            }
+        }
+        counters
+    }
+
+    fn group_of<T>(&self, zone: &Zone<T>) -> Option<usize> {
+        let length = zone.round_robin?.length.max(1);
+        self.groups
+            .iter()
+            .position(|group| group.length == length && group.keys == zone.keys)
+    }
+
+    /// Advances every round robin with a zone that `key` and `velocity`
+    /// reach, returning the note number of each one that advanced.
+    fn advance<T>(&self, zones: &[Zone<T>], key: u8, velocity: u8) -> Vec<Option<usize>> {
+        let mut reached = vec![false; self.groups.len()];
+        for zone in zones.iter().filter(|zone| zone.plays(key, velocity)) {
+            if let Some(group) = self.group_of(zone) {
+                reached[group] = true;
+            }
+        }
+        self.groups
+            .iter()
+            .zip(reached)
+            .map(|(group, reached)| {
+                reached.then(|| group.notes_played.fetch_add(1, Ordering::Relaxed))
+            })
+            .collect()
+    }
+}
+
+impl Clone for RoundRobinCounters {
+    /// Clones start counting from where the original is, but advance
+    /// independently.
+    fn clone(&self) -> Self {
+        Self {
+            groups: self
+                .groups
+                .iter()
+                .map(|group| RoundRobinGroup {
+                    length: group.length,
+                    keys: group.keys.clone(),
+                    notes_played: AtomicUsize::new(group.notes_played.load(Ordering::Relaxed)),
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Parameter {
     Value(f32),
@@ -221,16 +595,358 @@ pub enum Parameter {
     NoteStep,
     NoteVelocity,
     Envelope(EnvelopeConfiguration),
+    Lfo(LfoConfiguration),
+    Control(String),
+    Add(Vec<Parameter>),
+    Multiply(Vec<Parameter>),
+    Min(Vec<Parameter>),
+    Max(Vec<Parameter>),
+    Scale {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    Exponential {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    Clamp {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
 }
 
 impl Parameter {
-    pub fn instantiate(&self, controls: &ControlHandles) -> parameter::Parameter {
+    pub fn instantiate(&self, note: &Note, controls: &ControlHandles) -> parameter::Parameter {
         match self {
             Parameter::NoteHertz => parameter::Parameter::NoteHertz,
             Parameter::NoteStep => parameter::Parameter::NoteStep,
             Parameter::NoteVelocity => parameter::Parameter::NoteVelocity,
-            Parameter::Envelope(config) => config.as_parameter(controls),
+            Parameter::Envelope(config) => config.as_parameter(note, controls),
             Parameter::Value(value) => parameter::Parameter::Value(*value),
+            Parameter::Lfo(config) => config.as_parameter(),
+            Parameter::Control(name) => parameter::Parameter::Control(controls.bus().control(name)),
+            Parameter::Add(inputs) => {
+                parameter::Parameter::Add(instantiate_all(inputs, note, controls))
+            }
+            Parameter::Multiply(inputs) => {
+                parameter::Parameter::Multiply(instantiate_all(inputs, note, controls))
+            }
+            Parameter::Min(inputs) => {
+                parameter::Parameter::Min(instantiate_all(inputs, note, controls))
+            }
+            Parameter::Max(inputs) => {
+                parameter::Parameter::Max(instantiate_all(inputs, note, controls))
+            }
+            Parameter::Scale { input, min, max } => parameter::Parameter::Scale {
+                input: Box::new(input.instantiate(note, controls)),
+                min: *min,
+                max: *max,
+            },
+            Parameter::Exponential { input, min, max } => parameter::Parameter::Exponential {
+                input: Box::new(input.instantiate(note, controls)),
+                min: *min,
+                max: *max,
+            },
+            Parameter::Clamp { input, min, max } => parameter::Parameter::Clamp {
+                input: Box::new(input.instantiate(note, controls)),
+                min: *min,
+                max: *max,
+            },
+        }
+    }
+}
+
+fn instantiate_all(
+    inputs: &[Parameter],
+    note: &Note,
+    controls: &ControlHandles,
+) -> Vec<parameter::Parameter> {
+    inputs
+        .iter()
+        .map(|input| input.instantiate(note, controls))
+        .collect()
+}
+
+#[cfg(all(test, feature = "serialization"))]
+mod tests {
+    use super::*;
+    use crate::{
+        envelope::EnvelopeCurveError,
+        sampler::{FrameInfo, Sampler},
+        test_support::TempDir,
+    };
+
+    fn constant(value: f32) -> Node<()> {
+        Node::Oscillator {
+            function: OscillatorFunction::Square,
+            frequency: Parameter::Value(1.),
+            amplitude: Parameter::Value(value * 2.),
+            start_phase: StartPhase::Zero,
+        }
+    }
+
+    #[test]
+    fn zones_match_key_velocity_and_round_robin() {
+        let zone = |keys, velocities, round_robin, value| Zone {
+            keys,
+            velocities,
+            round_robin,
+            node: constant(value),
+        };
+        let round_robin = |position, length| Some(RoundRobin { position, length });
+        let zoned = Node::zoned(vec![
+            zone(0..=59, 0..=127, round_robin(1, 2), 1.),
+            zone(0..=59, 0..=127, round_robin(2, 2), 5.),
+            zone(60..=127, 0..=63, None, 2.),
+            zone(60..=127, 64..=127, round_robin(1, 2), 3.),
+            zone(60..=127, 64..=127, round_robin(2, 2), 4.),
+        ]);
+        let play = |zoned: &Node<()>, step, velocity| {
+            let note = Note::new(step, velocity);
+            let frame = FrameInfo {
+                clock: 0,
+                sample_rate: 44_100,
+                note,
+            };
+            zoned
+                .instantiate(&note, &ControlHandles::new())
+                .sample(&frame)
+                .map(|sample| sample.left)
+        };
+
+        assert_eq!(play(&zoned, 48., 100), Some(1.));
+        assert_eq!(play(&zoned, 60., 10), Some(2.));
+        // Each round robin only counts the notes that reach it
+        assert_eq!(play(&zoned, 60., 100), Some(3.));
+        assert_eq!(play(&zoned, 48., 100), Some(5.));
+        assert_eq!(play(&zoned, 60., 100), Some(4.));
+        assert_eq!(play(&zoned, 60., 10), Some(2.));
+        assert_eq!(play(&zoned, 60., 100), Some(3.));
+
+        // Clones keep their own counts
+        let clone = zoned.clone();
+        assert_eq!(play(&clone, 60., 100), Some(4.));
+        assert_eq!(play(&zoned, 60., 100), Some(4.));
+    }
+
+    #[test]
+    fn seeded_noise_differs_per_instance() {
+        let noise = || Node::<()>::Noise {
+            color: NoiseColor::White,
+            amplitude: Parameter::Value(1.),
+            seed: Some(7),
+            instances: Default::default(),
+        };
+        let render = |node: &Node<()>| {
+            let note = Note::new(60., 127);
+            let mut sampler = node.instantiate(&note, &ControlHandles::new());
+            (1..64)
+                .map(|clock| {
+                    sampler
+                        .sample(&FrameInfo {
+                            clock,
+                            sample_rate: 44_100,
+                            note,
+                        })
+                        .unwrap()
+                        .left
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let node = noise();
+        let first = render(&node);
+        assert_ne!(first, render(&node));
+        assert_eq!(first, render(&noise()));
+    }
+
+    #[test]
+    fn wavetable_paths_are_relative_to_instrument() {
+        let dir = TempDir::new("wavetable");
+        dir.write_wav("waves/square.wav", 44_100, &[0.5, -0.5]);
+        let path = dir.write(
+            "instrument.ron",
+            r#"Instrument(
+                name: "Wavetable",
+                envelopes: {},
+                nodes: {
+                    "output": Wavetable(
+                        files: ["waves/square.wav"],
+                        frequency: Value(100),
+                        amplitude: Value(2),
+                        morph: Value(0),
+                    ),
+                },
+            )"#,
+        );
+
+        let instrument = LoadedInstrument::<()>::open(path).unwrap();
+        let mut sampler = instrument.instantiate(&Note::default(), &ControlHandles::new());
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 44_100,
+            note: Note::default(),
+        };
+        assert_eq!(sampler.sample(&frame).unwrap().left, 0.5);
+
+        let missing = LoadedInstrument::<()>::open(dir.join("missing.ron"));
+        assert!(matches!(missing, Err(serialization::Error::Io(_))));
+    }
+
+    #[test]
+    fn samples_are_read_once_per_load() {
+        let dir = TempDir::new("sample-cache");
+        dir.write_wav("click.wav", 44_100, &[0.]);
+
+        let envelopes = HashMap::new();
+        let mut context = serialization::Context::<()>::new(&envelopes, dir.path());
+        let first = context.sample("click.wav").unwrap();
+        let second = context.sample("click.wav").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(matches!(
+            context.sample("missing.wav"),
+            Err(serialization::Error::Wav(_))
+        ));
+    }
+
+    #[test]
+    fn filters_and_parameters_load_from_ron() {
+        let spec = ron::from_str::<serialization::Instrument>(
+            r#"Instrument(
+                name: "Filtered",
+                envelopes: {},
+                nodes: {
+                    "saw": Oscillator(
+                        function: BandLimitedSawtooth,
+                        frequency: Multiply([NoteHertz, Value(2)]),
+                        amplitude: Lfo((rate: 5, depth: 0.2, offset: 0.8, key_sync: true)),
+                    ),
+                    "ladder": Ladder(
+                        cutoff: Exponential(input: NoteVelocity, min: 200, max: 4000),
+                        input: "saw",
+                    ),
+                    "output": Filter(
+                        design: Biquad,
+                        mode: HighPass,
+                        cutoff: Value(100),
+                        input: "ladder",
+                    ),
+                },
+            )"#,
+        )
+        .unwrap();
+        let instrument = LoadedInstrument::<()>::load(spec, ".").unwrap();
+        assert!(matches!(
+            &instrument.output,
+            Node::Filter {
+                design: FilterDesign::Biquad,
+                input,
+                ..
+            } if matches!(**input, Node::Ladder { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_parameter_ranges_fail_to_load() {
+        let load = |amplitude: &str| {
+            let spec = ron::from_str::<serialization::Instrument>(&format!(
+                r#"Instrument(
+                    name: "Ranged",
+                    envelopes: {{}},
+                    nodes: {{
+                        "output": Oscillator(
+                            function: Sine,
+                            frequency: Value(440),
+                            amplitude: {},
+                        ),
+                    }},
+                )"#,
+                amplitude
+            ))
+            .unwrap();
+            LoadedInstrument::<()>::load(spec, ".")
+        };
+
+        assert!(load("Clamp(input: NoteVelocity, min: 0, max: 1)").is_ok());
+        assert!(matches!(
+            load("Clamp(input: NoteVelocity, min: 1, max: 0)"),
+            Err(serialization::Error::InvalidRange {
+                parameter: "Clamp",
+                ..
+            })
+        ));
+        assert!(load("Exponential(input: NoteVelocity, min: -2, max: -1)").is_ok());
+        for (min, max) in [(0, 1), (-1, 1)] {
+            assert!(matches!(
+                load(&format!(
+                    "Exponential(input: NoteVelocity, min: {}, max: {})",
+                    min, max
+                )),
+                Err(serialization::Error::InvalidRange {
+                    parameter: "Exponential",
+                    ..
+                })
+            ));
         }
     }
+
+    #[test]
+    fn envelope_curves_load_from_ron() {
+        let spec = ron::from_str::<serialization::Instrument>(
+            r#"Instrument(
+                name: "Shaped",
+                envelopes: {
+                    "main": (
+                        attack: Some(Points([(0, 0), (5, 0.8), (10, 1)])),
+                        decay: Some(Path(
+                            start: (0, 1),
+                            segments: [
+                                Cubic(to: (100, 0.5), start_control: (0, 0.6), end_control: (50, 0.5)),
+                                Line((150, 0.4)),
+                            ],
+                        )),
+                        sustain: Some(Sustain(0.4)),
+                        scaling: (decay: (key: -0.3), level: (velocity: 1)),
+                    ),
+                    "broken": (attack: Some(Points([(0, 0)]))),
+                },
+                nodes: {},
+            )"#,
+        )
+        .unwrap();
+        let mut envelopes = spec.envelopes;
+        let broken = envelopes.remove("broken").unwrap();
+        let envelopes = LoadedInstrument::<()>::instantiate_envelopes(&envelopes).unwrap();
+        let main = &envelopes["main"];
+
+        let attack = &main.attack.segments;
+        assert_eq!(attack.len(), 2);
+        assert!((attack[0].duration - 0.005).abs() < 0.0001);
+        assert_eq!(attack[0].end_value, 0.8);
+        assert_eq!(main.attack.terminal_value(), Some(1.));
+
+        // The bezier is flattened into several lines
+        let decay = &main.decay.segments;
+        assert!(decay.len() > 2);
+        assert_eq!(main.decay.start_value(), Some(1.));
+        assert_eq!(main.decay.terminal_value(), Some(0.4));
+        let duration = decay.iter().map(|segment| segment.duration).sum::<f32>();
+        assert!((duration - 0.15).abs() < 0.0001);
+        assert_eq!(main.scaling.decay.key, -0.3);
+        assert_eq!(main.scaling.level.velocity, 1.);
+        assert_eq!(main.scaling.key_center, 60.);
+
+        let broken = LoadedInstrument::<()>::instantiate_envelopes(
+            &std::iter::once(("broken".to_owned(), broken)).collect(),
+        );
+        assert!(matches!(
+            broken,
+            Err(serialization::Error::EnvelopeCurveError(
+                EnvelopeCurveError::TooFewPoints
+            ))
+        ));
+    }
 }
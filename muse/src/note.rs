@@ -1,4 +1,21 @@
 pub use pitch_calc::{Letter, Octave};
+use std::str::FromStr;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum NoteParseError {
+    #[error("missing note letter")]
+    MissingLetter,
+    #[error("invalid note letter '{0}'")]
+    InvalidLetter(char),
+    #[error("invalid octave")]
+    InvalidOctave,
+    #[error("invalid cents offset")]
+    InvalidCents,
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Note {
@@ -18,10 +35,66 @@ impl Note {
         Self { hertz, velocity }
     }
 
+    pub fn from_key(key: u8, velocity: u8) -> Self {
+        Self::new(key as f32, velocity)
+    }
+
+    /// Parses a note name such as `C#4` or `Bb3`, which may end with a cents
+    /// offset such as `A4-13c`.
+    pub fn named(name: &str, velocity: u8) -> Result<Self, NoteParseError> {
+        Ok(name.parse::<Self>()?.with_velocity(velocity))
+    }
+
+    pub fn with_velocity(mut self, velocity: u8) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// This note shifted by `semitones`, which may be fractional.
+    pub fn bent(&self, semitones: f32) -> Self {
+        Self {
+            hertz: self.hertz * 2f32.powf(semitones / 12.),
+            velocity: self.velocity,
+        }
+    }
+
     pub fn step(&self) -> f32 {
         pitch_calc::step_from_hz(self.hertz())
     }
 
+    /// The nearest MIDI key, if it is within the MIDI range.
+    pub fn key(&self) -> Option<u8> {
+        let key = self.step().round();
+        if (0. ..=127.).contains(&key) {
+            Some(key as u8)
+        } else {
+            None
+        }
+    }
+
+    /// How far this note is from the nearest key, between -50 and 50 cents.
+    pub fn cents(&self) -> f32 {
+        let step = self.step();
+        (step - step.round()) * 100.
+    }
+
+    /// The name of the nearest key using sharps, such as `C#4`. Notes more
+    /// than half a cent from the key include the offset in whole cents, such
+    /// as `A4+14c`.
+    pub fn name(&self) -> String {
+        let step = self.step().round() as i32;
+        let mut name = format!(
+            "{}{}",
+            NOTE_NAMES[step.rem_euclid(12) as usize],
+            step.div_euclid(12) - 1
+        );
+        let cents = self.cents().round() as i32;
+        if cents != 0 {
+            name.push_str(&format!("{:+}c", cents));
+        }
+        name
+    }
+
     pub fn hertz(&self) -> f32 {
         self.hertz
     }
@@ -35,13 +108,99 @@ impl Note {
     }
 
     pub fn letter_octave(&self) -> (Letter, Octave) {
-        pitch_calc::letter_octave_from_step(self.step() as f32)
+        pitch_calc::letter_octave_from_step(self.step())
     }
 }
 
 impl std::fmt::Display for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let (letter, octave) = self.letter_octave();
-        f.write_fmt(format_args!("{:?}{}({})", letter, octave, self.velocity))
+        f.write_fmt(format_args!("{}({})", self.name(), self.velocity))
+    }
+}
+
+/// Parses note names as described by [`Note::named`], at full velocity.
+impl FromStr for Note {
+    type Err = NoteParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars().peekable();
+        let letter = chars.next().ok_or(NoteParseError::MissingLetter)?;
+        let mut semitone = match letter.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return Err(NoteParseError::InvalidLetter(letter)),
+        };
+        while let Some(accidental) = chars.peek() {
+            match accidental {
+                '#' | '♯' => semitone += 1,
+                'b' | '♭' => semitone -= 1,
+                _ => break,
+            }
+            chars.next();
+        }
+
+        let rest = chars.collect::<String>();
+        let octave_length = rest
+            .char_indices()
+            .find(|&(index, c)| !(c.is_ascii_digit() || (index == 0 && c == '-')))
+            .map_or(rest.len(), |(index, _)| index);
+        let (octave, cents) = rest.split_at(octave_length);
+        let octave = octave
+            .parse::<i32>()
+            .map_err(|_| NoteParseError::InvalidOctave)?;
+        let cents = if cents.is_empty() {
+            0.
+        } else if cents.starts_with(['+', '-']) {
+            cents
+                .strip_suffix('c')
+                .unwrap_or(cents)
+                .parse::<f32>()
+                .map_err(|_| NoteParseError::InvalidCents)?
+        } else {
+            return Err(NoteParseError::InvalidCents);
+        };
+
+        let step = (octave + 1) * 12 + semitone;
+        Ok(Self::new(step as f32 + cents / 100., 127))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let step = |name: &str| name.parse::<Note>().unwrap().step();
+        assert!((step("C4") - 60.).abs() < 0.001);
+        assert!((step("A4") - 69.).abs() < 0.001);
+        assert!((step("c#4") - 61.).abs() < 0.001);
+        assert!((step("Bb3") - 58.).abs() < 0.001);
+        assert!((step("Cb4") - 59.).abs() < 0.001);
+        assert!((step("C-1") - 0.).abs() < 0.001);
+        assert!((step("A4+25c") - 69.25).abs() < 0.001);
+        assert!((step("A4-13.5") - 68.865).abs() < 0.001);
+
+        assert_eq!(Note::named("E4", 80).unwrap().velocity(), 80);
+        assert_eq!(
+            "H4".parse::<Note>(),
+            Err(NoteParseError::InvalidLetter('H'))
+        );
+        assert_eq!("C".parse::<Note>(), Err(NoteParseError::InvalidOctave));
+        assert_eq!("C4x".parse::<Note>(), Err(NoteParseError::InvalidCents));
+        assert_eq!("".parse::<Note>(), Err(NoteParseError::MissingLetter));
+
+        assert_eq!(Note::from_key(61, 127).name(), "C#4");
+        assert_eq!(Note::from_key(0, 127).name(), "C-1");
+        assert_eq!(Note::new(69.14, 127).name(), "A4+14c");
+        assert_eq!(Note::new(69.6, 127).name(), "A#4-40c");
+        assert_eq!(Note::from_hertz(440., 100).to_string(), "A4(100)");
+        assert_eq!(Note::from_hertz(440., 127).key(), Some(69));
+        assert_eq!(Note::from_hertz(20_000., 127).key(), None);
     }
 }
@@ -1,22 +1,215 @@
-use crate::{envelope::Envelope, sampler::FrameInfo};
+use crate::{envelope::Envelope, instrument::ControlValue, lfo::Lfo, sampler::FrameInfo};
 
 #[derive(Debug, Clone)]
 pub enum Parameter {
     Value(f32),
     Envelope(Box<Envelope>),
+    Lfo(Box<Lfo>),
+    /// A live value from a [`ControlBus`](crate::instrument::ControlBus).
+    Control(ControlValue),
     NoteHertz,
     NoteVelocity,
     NoteStep,
+    Add(Vec<Parameter>),
+    Multiply(Vec<Parameter>),
+    Min(Vec<Parameter>),
+    Max(Vec<Parameter>),
+    /// Maps `input` from 0..1 onto `min..max`.
+    Scale {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    /// Maps `input` from 0..1 onto `min..max` exponentially, which suits
+    /// frequencies and gains. If `min` and `max` don't have the same sign
+    /// this falls back to [`Parameter::Scale`].
+    Exponential {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
+    Clamp {
+        input: Box<Parameter>,
+        min: f32,
+        max: f32,
+    },
 }
 
 impl Parameter {
+    /// Whether any part of this parameter is an envelope, which responds to
+    /// the note being released.
+    pub fn has_envelope(&self) -> bool {
+        match self {
+            Self::Envelope(_) => true,
+            Self::Add(inputs) | Self::Multiply(inputs) | Self::Min(inputs) | Self::Max(inputs) => {
+                inputs.iter().any(Self::has_envelope)
+            }
+            Self::Scale { input, .. }
+            | Self::Exponential { input, .. }
+            | Self::Clamp { input, .. } => input.has_envelope(),
+            _ => false,
+        }
+    }
+
     pub fn next(&mut self, frame: &FrameInfo) -> Option<f32> {
         match self {
             Self::Value(value) => Some(*value),
             Self::Envelope(envelope) => envelope.next(frame),
+            Self::Lfo(lfo) => Some(lfo.next(frame)),
+            Self::Control(value) => Some(value.load()),
             Self::NoteHertz => Some(frame.note.hertz()),
-            Self::NoteStep => Some(frame.note.step() as f32),
+            Self::NoteStep => Some(frame.note.step()),
             Self::NoteVelocity => Some(frame.note.velocity_percent()),
+            Self::Add(inputs) => combine(inputs, frame, |a, b| a + b),
+            Self::Multiply(inputs) => combine(inputs, frame, |a, b| a * b),
+            Self::Min(inputs) => combine(inputs, frame, f32::min),
+            Self::Max(inputs) => combine(inputs, frame, f32::max),
+            Self::Scale { input, min, max } => {
+                input.next(frame).map(|value| *min + (*max - *min) * value)
+            }
+            Self::Exponential { input, min, max } => input.next(frame).map(|value| {
+                // The curve is undefined when the range touches or crosses
+                // zero, so use a line instead of producing NaN or infinity
+                let ratio = *max / *min;
+                if *min * *max > 0. && ratio.is_finite() {
+                    *min * ratio.powf(value)
+                } else {
+                    *min + (*max - *min) * value
+                }
+            }),
+            // Unlike f32::clamp, this doesn't panic on an invalid range
+            Self::Clamp { input, min, max } => {
+                input.next(frame).map(|value| value.max(*min).min(*max))
+            }
         }
     }
 }
+
+/// Advances every input, even once one has finished, so that they stay in
+/// step with each other. Returns None if any input has finished or if there
+/// are no inputs.
+fn combine<F: Fn(f32, f32) -> f32>(
+    inputs: &mut [Parameter],
+    frame: &FrameInfo,
+    operation: F,
+) -> Option<f32> {
+    let (combined, finished) =
+        inputs
+            .iter_mut()
+            .fold((None, false), |(combined, finished), input| {
+                match input.next(frame) {
+                    Some(value) => (
+                        Some(combined.map_or(value, |combined| operation(combined, value))),
+                        finished,
+                    ),
+                    None => (combined, true),
+                }
+            });
+    combined.filter(|_| !finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Note;
+
+    #[test]
+    fn expressions() {
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 44_100,
+            note: Note::new(69., 127),
+        };
+        let value = |mut parameter: Parameter| parameter.next(&frame).unwrap();
+
+        assert_eq!(
+            value(Parameter::Multiply(vec![
+                Parameter::NoteHertz,
+                Parameter::Value(2.)
+            ])),
+            880.
+        );
+        assert_eq!(
+            value(Parameter::Add(vec![
+                Parameter::Value(1.),
+                Parameter::Value(2.),
+                Parameter::Value(3.)
+            ])),
+            6.
+        );
+        assert_eq!(
+            value(Parameter::Min(vec![
+                Parameter::Value(1.),
+                Parameter::Value(-2.)
+            ])),
+            -2.
+        );
+        assert_eq!(
+            value(Parameter::Max(vec![
+                Parameter::Value(1.),
+                Parameter::Value(-2.)
+            ])),
+            1.
+        );
+        assert_eq!(
+            value(Parameter::Scale {
+                input: Box::new(Parameter::Value(0.5)),
+                min: 200.,
+                max: 4_000.,
+            }),
+            2_100.
+        );
+        assert_eq!(
+            value(Parameter::Exponential {
+                input: Box::new(Parameter::Value(0.5)),
+                min: 100.,
+                max: 10_000.,
+            }),
+            1_000.
+        );
+        assert_eq!(
+            value(Parameter::Clamp {
+                input: Box::new(Parameter::NoteStep),
+                min: 0.,
+                max: 60.,
+            }),
+            60.
+        );
+        assert_eq!(Parameter::Max(Vec::new()).next(&frame), None);
+    }
+
+    #[test]
+    fn invalid_clamps_do_not_panic() {
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 44_100,
+            note: Note::new(69., 127),
+        };
+        let clamp = || Parameter::Clamp {
+            input: Box::new(Parameter::NoteStep),
+            min: 100.,
+            max: 0.,
+        };
+        assert_eq!(clamp().next(&frame), Some(0.));
+        // A finished input finishes the combination after the rest advance
+        let mut parameter = Parameter::Add(vec![Parameter::Max(Vec::new()), clamp()]);
+        assert_eq!(parameter.next(&frame), None);
+    }
+
+    #[test]
+    fn invalid_exponentials_are_linear() {
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 44_100,
+            note: Note::new(69., 127),
+        };
+        let exponential = |min, max| Parameter::Exponential {
+            input: Box::new(Parameter::Value(0.5)),
+            min,
+            max,
+        };
+        assert_eq!(exponential(0., 100.).next(&frame), Some(50.));
+        assert_eq!(exponential(100., 0.).next(&frame), Some(50.));
+        assert_eq!(exponential(-1., 1.).next(&frame), Some(0.));
+    }
+}
@@ -1,17 +1,23 @@
 mod add;
 mod amplify;
+mod filter;
 mod max;
 mod multiply;
+mod noise;
 mod oscillator;
 mod pan;
+mod sample_player;
 mod unison;
 use crate::Note;
 pub use add::*;
 pub use amplify::*;
+pub use filter::*;
 pub use max::*;
 pub use multiply::*;
+pub use noise::*;
 pub use oscillator::*;
 pub use pan::*;
+pub use sample_player::*;
 pub use unison::*;
 
 fn clampf(value: f32, min: f32, max: f32) -> f32 {
@@ -181,7 +187,7 @@ impl std::iter::Sum for Sample {
 
 pub mod prelude {
     pub use super::{
-        add::*, amplify::*, max::*, multiply::*, oscillator::*, pan::*, PreparableSampler,
-        PreparedSampler, Sample, Sampler,
+        add::*, amplify::*, max::*, multiply::*, noise::*, oscillator::*, pan::*,
+        PreparableSampler, PreparedSampler, Sample, Sampler,
     };
 }
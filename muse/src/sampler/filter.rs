@@ -0,0 +1,295 @@
+use crate::{
+    parameter::Parameter,
+    sampler::{FrameInfo, PreparableSampler, PreparedSampler, Sample, Sampler},
+};
+use std::f32::consts::PI;
+
+mod ladder;
+pub use ladder::LadderFilter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub enum FilterMode {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+/// Keeps the cutoff below the Nyquist frequency and the resonance positive,
+/// which both filters need to remain stable.
+fn limit(cutoff: f32, resonance: f32, sample_rate: u32) -> (f32, f32) {
+    (
+        cutoff.clamp(10., sample_rate as f32 * 0.49),
+        resonance.max(0.1),
+    )
+}
+
+/// A second-order filter using the coefficients from Robert
+/// Bristow-Johnson's Audio EQ Cookbook. `resonance` is the filter's Q, where
+/// 0.707 has no resonant peak.
+#[derive(Debug)]
+pub struct Biquad {
+    mode: FilterMode,
+    cutoff: Parameter,
+    resonance: Parameter,
+    source: PreparedSampler,
+    coefficients: Option<BiquadCoefficients>,
+    left: BiquadState,
+    right: BiquadState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BiquadCoefficients {
+    cutoff: f32,
+    resonance: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    pub fn new<T: PreparableSampler>(
+        mode: FilterMode,
+        cutoff: Parameter,
+        resonance: Parameter,
+        source: T,
+    ) -> Self {
+        Self {
+            mode,
+            cutoff,
+            resonance,
+            source: source.prepare(),
+            coefficients: None,
+            left: BiquadState::default(),
+            right: BiquadState::default(),
+        }
+    }
+
+    fn coefficients(
+        &mut self,
+        cutoff: f32,
+        resonance: f32,
+        sample_rate: u32,
+    ) -> BiquadCoefficients {
+        if let Some(coefficients) = self.coefficients {
+            if coefficients.cutoff == cutoff && coefficients.resonance == resonance {
+                return coefficients;
+            }
+        }
+
+        let omega = 2. * PI * cutoff / sample_rate as f32;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2. * resonance);
+        let (b0, b1, b2) = match self.mode {
+            FilterMode::LowPass => ((1. - cos) / 2., 1. - cos, (1. - cos) / 2.),
+            FilterMode::HighPass => ((1. + cos) / 2., -(1. + cos), (1. + cos) / 2.),
+            FilterMode::BandPass => (alpha, 0., -alpha),
+            FilterMode::Notch => (1., -2. * cos, 1.),
+        };
+        let a0 = 1. + alpha;
+        let coefficients = BiquadCoefficients {
+            cutoff,
+            resonance,
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: -2. * cos / a0,
+            a2: (1. - alpha) / a0,
+        };
+        self.coefficients = Some(coefficients);
+        coefficients
+    }
+}
+
+impl BiquadState {
+    fn process(&mut self, input: f32, c: &BiquadCoefficients) -> f32 {
+        let output =
+            c.b0 * input + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        output
+    }
+}
+
+impl Sampler for Biquad {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let sample = self.source.sample(frame)?;
+        let (cutoff, resonance) = limit(
+            self.cutoff.next(frame)?,
+            self.resonance.next(frame)?,
+            frame.sample_rate,
+        );
+        let coefficients = self.coefficients(cutoff, resonance, frame.sample_rate);
+
+        Some(Sample {
+            left: self.left.process(sample.left, &coefficients),
+            right: self.right.process(sample.right, &coefficients),
+        })
+    }
+}
+
+/// A state-variable filter using the trapezoidal integration described by
+/// Andrew Simper. It stays stable while its cutoff is swept quickly, making it
+/// a better fit than [`Biquad`] for envelopes and modulation. `resonance` is
+/// the filter's Q.
+#[derive(Debug)]
+pub struct StateVariableFilter {
+    mode: FilterMode,
+    cutoff: Parameter,
+    resonance: Parameter,
+    source: PreparedSampler,
+    left: StateVariableState,
+    right: StateVariableState,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct StateVariableState {
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl StateVariableFilter {
+    pub fn new<T: PreparableSampler>(
+        mode: FilterMode,
+        cutoff: Parameter,
+        resonance: Parameter,
+        source: T,
+    ) -> Self {
+        Self {
+            mode,
+            cutoff,
+            resonance,
+            source: source.prepare(),
+            left: StateVariableState::default(),
+            right: StateVariableState::default(),
+        }
+    }
+}
+
+impl StateVariableState {
+    fn process(&mut self, input: f32, g: f32, k: f32, mode: FilterMode) -> f32 {
+        let a1 = 1. / (1. + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2. * v1 - self.ic1eq;
+        self.ic2eq = 2. * v2 - self.ic2eq;
+
+        match mode {
+            FilterMode::LowPass => v2,
+            FilterMode::BandPass => k * v1,
+            FilterMode::HighPass => input - k * v1 - v2,
+            FilterMode::Notch => input - k * v1,
+        }
+    }
+}
+
+impl Sampler for StateVariableFilter {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let sample = self.source.sample(frame)?;
+        let (cutoff, resonance) = limit(
+            self.cutoff.next(frame)?,
+            self.resonance.next(frame)?,
+            frame.sample_rate,
+        );
+        let g = (PI * cutoff / frame.sample_rate as f32).tan();
+        let k = 1. / resonance;
+
+        Some(Sample {
+            left: self.left.process(sample.left, g, k, self.mode),
+            right: self.right.process(sample.right, g, k, self.mode),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        manager::Device,
+        sampler::{Oscillator, Sine},
+        Note,
+    };
+    use std::time::Duration;
+
+    type Constructor = fn(FilterMode, PreparedSampler) -> PreparedSampler;
+
+    /// The RMS level of a filtered full-scale sine wave relative to the
+    /// unfiltered wave, ignoring the first 100ms while the filter settles.
+    pub(super) fn response(
+        frequency: f32,
+        filter: impl FnOnce(PreparedSampler) -> PreparedSampler,
+    ) -> f32 {
+        let device = Device::offline(44_100);
+        let sine = Oscillator::<Sine>::new(Parameter::Value(frequency), Parameter::Value(2.));
+        let _handle = device
+            .play(filter(sine.prepare()), Note::default())
+            .unwrap();
+        let samples = device.render_duration(Duration::from_millis(500)).unwrap();
+        let settled = &samples[4_410..];
+        let rms =
+            (settled.iter().map(|s| s.left * s.left).sum::<f32>() / settled.len() as f32).sqrt();
+        rms / std::f32::consts::FRAC_1_SQRT_2
+    }
+
+    #[test]
+    fn frequency_response() {
+        let filters: [Constructor; 2] = [
+            |mode, source| {
+                Biquad::new(
+                    mode,
+                    Parameter::Value(1_000.),
+                    Parameter::Value(0.707),
+                    source,
+                )
+                .prepare()
+            },
+            |mode, source| {
+                StateVariableFilter::new(
+                    mode,
+                    Parameter::Value(1_000.),
+                    Parameter::Value(0.707),
+                    source,
+                )
+                .prepare()
+            },
+        ];
+
+        for filter in &filters {
+            for &mode in &[
+                FilterMode::LowPass,
+                FilterMode::HighPass,
+                FilterMode::BandPass,
+                FilterMode::Notch,
+            ] {
+                let low = response(100., |source| filter(mode, source));
+                let center = response(1_000., |source| filter(mode, source));
+                let high = response(10_000., |source| filter(mode, source));
+                match mode {
+                    FilterMode::LowPass => assert!(low > 0.95 && high < 0.02),
+                    FilterMode::HighPass => assert!(low < 0.02 && high > 0.95),
+                    FilterMode::BandPass => assert!(center > 0.95 && low < 0.2 && high < 0.2),
+                    FilterMode::Notch => assert!(center < 0.05 && low > 0.95 && high > 0.95),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use super::limit;
+use crate::{
+    parameter::Parameter,
+    sampler::{FrameInfo, PreparableSampler, PreparedSampler, Sample, Sampler},
+};
+use std::f32::consts::PI;
+
+/// The ladder runs at twice the sample rate to keep its feedback loop
+/// stable and in tune near the top of the audio range, and to reduce the
+/// aliasing of the harmonics its saturation adds. The input is upsampled by
+/// linear interpolation, and the output is downsampled by averaging each
+/// pair of steps.
+const OVERSAMPLING: usize = 2;
+
+/// A four-pole, 24dB/octave low-pass filter modeled on the transistor ladder
+/// from Moog synthesizers. Each stage saturates, so increasing `drive` adds
+/// harmonics as the input gets louder. `resonance` ranges from 0 to 1, and
+/// the filter oscillates at its cutoff once it is a little above 1.
+#[derive(Debug)]
+pub struct LadderFilter {
+    cutoff: Parameter,
+    resonance: Parameter,
+    drive: Parameter,
+    source: PreparedSampler,
+    left: LadderState,
+    right: LadderState,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LadderState {
+    stages: [f32; 4],
+    last_input: f32,
+}
+
+impl LadderFilter {
+    pub fn new<T: PreparableSampler>(
+        cutoff: Parameter,
+        resonance: Parameter,
+        drive: Parameter,
+        source: T,
+    ) -> Self {
+        Self {
+            cutoff,
+            resonance,
+            drive,
+            source: source.prepare(),
+            left: LadderState::default(),
+            right: LadderState::default(),
+        }
+    }
+}
+
+impl LadderState {
+    fn process(&mut self, input: f32, g: f32, feedback: f32, drive: f32) -> f32 {
+        let mut output = 0.;
+        for step in 1..=OVERSAMPLING {
+            let blend = step as f32 / OVERSAMPLING as f32;
+            let input = self.last_input + (input - self.last_input) * blend;
+            output += self.step(input, g, feedback, drive);
+        }
+        self.last_input = input;
+        output / OVERSAMPLING as f32
+    }
+
+    fn step(&mut self, input: f32, g: f32, feedback: f32, drive: f32) -> f32 {
+        let mut stage_input = (drive * input - feedback * self.stages[3]).tanh();
+        for stage in self.stages.iter_mut() {
+            *stage += g * (stage_input - stage.tanh());
+            stage_input = stage.tanh();
+        }
+        self.stages[3]
+    }
+}
+
+impl Sampler for LadderFilter {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let sample = self.source.sample(frame)?;
+        let (cutoff, _) = limit(self.cutoff.next(frame)?, 1., frame.sample_rate);
+        let feedback = 4. * self.resonance.next(frame)?.max(0.);
+        let drive = self.drive.next(frame)?.max(0.);
+        let sample_rate = frame.sample_rate as f32 * OVERSAMPLING as f32;
+        let g = 1. - (-2. * PI * cutoff / sample_rate).exp();
+
+        Some(Sample {
+            left: self.left.process(sample.left, g, feedback, drive),
+            right: self.right.process(sample.right, g, feedback, drive),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::tests::response, *};
+    use crate::{manager::Device, sampler::Amplify, Note};
+    use std::time::Duration;
+
+    fn ladder(resonance: f32, drive: f32) -> impl FnOnce(PreparedSampler) -> PreparedSampler {
+        move |source| {
+            LadderFilter::new(
+                Parameter::Value(1_000.),
+                Parameter::Value(resonance),
+                Parameter::Value(drive),
+                source,
+            )
+            .prepare()
+        }
+    }
+
+    #[test]
+    fn frequency_response() {
+        // A quiet input stays out of the saturating region.
+        let quiet = |source| ladder(0., 1.)(Amplify::new(Parameter::Value(0.1), source).prepare());
+        let pass_band = response(100., quiet);
+        assert!((pass_band - 0.1).abs() < 0.01);
+        // Two octaves above the cutoff is attenuated by roughly 48dB.
+        assert!(response(4_000., quiet) < pass_band / 100.);
+        assert!(response(10_000., quiet) < pass_band / 1_000.);
+
+        // Resonance boosts the cutoff relative to the pass band.
+        let resonant =
+            |source| ladder(0.8, 1.)(Amplify::new(Parameter::Value(0.1), source).prepare());
+        assert!(response(1_000., resonant) > response(100., resonant) * 2.);
+    }
+
+    #[test]
+    fn drive_saturates() {
+        // Overdriving a loud input compresses it rather than amplifying it.
+        let clean = response(100., ladder(0., 1.));
+        let driven = response(100., ladder(0., 10.));
+        assert!(driven > clean);
+        assert!(driven < clean * 2.);
+    }
+
+    #[derive(Debug)]
+    struct Impulse(bool);
+
+    impl Sampler for Impulse {
+        fn sample(&mut self, _frame: &FrameInfo) -> Option<Sample> {
+            let value = if self.0 { 0. } else { 1. };
+            self.0 = true;
+            Some(Sample {
+                left: value,
+                right: value,
+            })
+        }
+    }
+
+    #[test]
+    fn self_oscillation() {
+        let device = Device::offline(44_100);
+        let _handle = device
+            .play(ladder(1.2, 1.)(Impulse(false).prepare()), Note::default())
+            .unwrap();
+        let samples = device.render_duration(Duration::from_secs(1)).unwrap();
+
+        // Long after the impulse, the filter is still ringing at its cutoff.
+        let tail = &samples[22_050..];
+        let peak = tail.iter().map(|s| s.left.abs()).fold(0f32, f32::max);
+        assert!(peak > 0.1);
+        let crossings = tail
+            .windows(2)
+            .filter(|pair| pair[0].left < 0. && pair[1].left >= 0.)
+            .count();
+        // Half a second at 1kHz
+        assert!((450..=550).contains(&crossings));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    parameter::Parameter,
+    sampler::{FrameInfo, Sample, Sampler},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub enum NoiseColor {
+    /// Equal power at every frequency.
+    White,
+    /// Power falls off by 3dB per octave.
+    Pink,
+    /// Power falls off by 6dB per octave.
+    Brown,
+}
+
+#[derive(Debug)]
+pub struct Noise {
+    color: NoiseColor,
+    amplitude: Parameter,
+    rng: SmallRng,
+    filter: [f32; 7],
+}
+
+impl Noise {
+    /// Creates a noise source. Sources created with the same `seed` produce
+    /// the same output, and `None` picks a random seed.
+    pub fn new(color: NoiseColor, amplitude: Parameter, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => SmallRng::seed_from_u64(seed),
+            None => SmallRng::from_entropy(),
+        };
+        Self {
+            color,
+            amplitude,
+            rng,
+            filter: [0.; 7],
+        }
+    }
+
+    fn next_value(&mut self) -> f32 {
+        let white = self.rng.gen_range(-1f32..=1.);
+        match self.color {
+            NoiseColor::White => white,
+            NoiseColor::Pink => {
+                // Paul Kellet's refined pinking filter
+                let b = &mut self.filter;
+                b[0] = 0.99886 * b[0] + white * 0.055_517_9;
+                b[1] = 0.99332 * b[1] + white * 0.075_075_9;
+                b[2] = 0.96900 * b[2] + white * 0.153_852;
+                b[3] = 0.86650 * b[3] + white * 0.310_485_6;
+                b[4] = 0.55000 * b[4] + white * 0.532_952_2;
+                b[5] = -0.7616 * b[5] - white * 0.016_898;
+                let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+                b[6] = white * 0.115_926;
+                pink * 0.11
+            }
+            NoiseColor::Brown => {
+                // A leaky integrator keeps the random walk from drifting away
+                let brown = &mut self.filter[0];
+                *brown = (*brown + 0.02 * white) / 1.02;
+                *brown * 3.5
+            }
+        }
+    }
+}
+
+impl Sampler for Noise {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let amplitude = self.amplitude.next(frame)?;
+        let value = self.next_value();
+
+        Some(Sample {
+            left: amplitude * value / 2.0,
+            right: amplitude * value / 2.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Note;
+
+    fn render(color: NoiseColor, seed: Option<u64>) -> Vec<f32> {
+        let mut noise = Noise::new(color, Parameter::Value(2.), seed);
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 44_100,
+            note: Note::default(),
+        };
+        (0..4_410)
+            .map(|_| noise.sample(&frame).unwrap().left)
+            .collect()
+    }
+
+    #[test]
+    fn seeded_noise_is_deterministic() {
+        for &color in &[NoiseColor::White, NoiseColor::Pink, NoiseColor::Brown] {
+            let samples = render(color, Some(42));
+            assert_eq!(samples, render(color, Some(42)));
+            assert_ne!(samples, render(color, Some(43)));
+            assert!(samples.iter().all(|value| value.abs() <= 1.));
+        }
+    }
+}
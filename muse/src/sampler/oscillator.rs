@@ -2,24 +2,68 @@ use crate::sampler::{FrameInfo, Sample, Sampler};
 use lazy_static::lazy_static;
 use std::{f32::consts::PI, time::Instant};
 
+mod band_limited;
+mod pulse;
 mod sawtooth;
 mod sine;
 mod square;
 mod triangle;
+mod wavetable;
 use crate::parameter::Parameter;
+pub use band_limited::{BandLimitedSawtooth, BandLimitedSquare, BandLimitedTriangle};
+pub use pulse::Pulse;
 pub use sawtooth::Sawtooth;
 pub use sine::Sine;
 pub use square::Square;
 pub use triangle::Triangle;
+pub use wavetable::{Wavetable, WavetableData};
 
 lazy_static! {
     static ref STARTUP_INSTANT: Instant = Instant::now();
 }
 
+/// Where in its cycle an oscillator begins.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StartPhase {
+    #[default]
+    Zero,
+    /// A fraction of a cycle, from 0 to 1.
+    Fixed(f32),
+    /// A different random phase for every note.
+    Random,
+}
+
+/// Tracks the position within a waveform's cycle. Because the phase advances
+/// by the current frequency each frame, frequency changes don't cause the
+/// waveform to jump.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseAccumulator {
+    phase: f32,
+}
+
+impl PhaseAccumulator {
+    pub fn new(start: StartPhase) -> Self {
+        let phase = match start {
+            StartPhase::Zero => 0.,
+            StartPhase::Fixed(phase) => phase.rem_euclid(1.),
+            StartPhase::Random => rand::random(),
+        };
+        Self { phase }
+    }
+
+    /// Returns the current phase as a fraction of a cycle, then advances it.
+    pub fn next(&mut self, frequency: f32, sample_rate: u32) -> f32 {
+        let current = self.phase;
+        self.phase = (self.phase + frequency / sample_rate as f32).rem_euclid(1.);
+        current
+    }
+}
+
 #[derive(Debug)]
 pub struct Oscillator<T> {
     frequency: Parameter,
     amplitude: Parameter,
+    phase: PhaseAccumulator,
     _of: std::marker::PhantomData<T>,
 }
 
@@ -28,13 +72,25 @@ impl<T> Oscillator<T> {
         Self {
             frequency,
             amplitude,
-            _of: std::marker::PhantomData::default(),
+            phase: PhaseAccumulator::new(StartPhase::Zero),
+            _of: std::marker::PhantomData,
         }
     }
+
+    pub fn with_start_phase(mut self, start: StartPhase) -> Self {
+        self.phase = PhaseAccumulator::new(start);
+        self
+    }
 }
 
 pub trait OscillatorFunction: Send + Sync + std::fmt::Debug {
     fn compute_sample(value: f32) -> f32;
+
+    /// Computes a sample knowing how far `value` advances each frame, which
+    /// band-limited functions use to avoid aliasing.
+    fn compute_sample_with_increment(value: f32, _increment: f32) -> f32 {
+        Self::compute_sample(value)
+    }
 }
 
 impl<T> Sampler for Oscillator<T>
@@ -42,11 +98,10 @@ where
     T: OscillatorFunction + 'static,
 {
     fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
-        let current_sample = frame.clock as f32 / frame.sample_rate as f32;
         let frequency = self.frequency.next(frame)?;
-        let value = current_sample * frequency * 2.0 * std::f32::consts::PI;
-        let value = value % (2.0 * PI);
-        let sample = T::compute_sample(value);
+        let value = self.phase.next(frequency, frame.sample_rate) * 2.0 * PI;
+        let increment = frequency / frame.sample_rate as f32 * 2.0 * PI;
+        let sample = T::compute_sample_with_increment(value, increment);
 
         self.amplitude.next(frame).map(|amplification| Sample {
             left: amplification * sample / 2.0,
@@ -54,3 +109,31 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Note;
+
+    #[test]
+    fn frequency_changes_are_continuous() {
+        let mut phase = PhaseAccumulator::new(StartPhase::Fixed(0.25));
+        assert_eq!(phase.next(100., 1_000), 0.25);
+        assert!((phase.next(200., 1_000) - 0.35).abs() < 1e-6);
+        assert!((phase.next(100., 1_000) - 0.55).abs() < 1e-6);
+        assert!((phase.next(0., 1_000) - 0.65).abs() < 1e-6);
+    }
+
+    #[test]
+    fn independent_of_clock() {
+        let mut oscillator = Oscillator::<Sine>::new(Parameter::Value(1.), Parameter::Value(2.))
+            .with_start_phase(StartPhase::Fixed(0.25));
+        let frame = FrameInfo {
+            clock: usize::MAX,
+            sample_rate: 44_100,
+            note: Note::default(),
+        };
+        let sample = oscillator.sample(&frame).unwrap();
+        assert!((sample.left - 1.).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,131 @@
+use super::OscillatorFunction;
+use std::f32::consts::PI;
+
+/// The polynomial approximation of a band-limited step's residual. `phase` and
+/// `increment` are fractions of a cycle, and the step occurs at a phase of 0.
+pub(crate) fn poly_blep(phase: f32, increment: f32) -> f32 {
+    if phase < increment {
+        let x = phase / increment;
+        x + x - x * x - 1.
+    } else if phase > 1. - increment {
+        let x = (phase - 1.) / increment;
+        x * x + x + x + 1.
+    } else {
+        0.
+    }
+}
+
+/// The integral of [`poly_blep`], which smooths changes in slope rather than
+/// steps. Multiply by the change in slope and the increment.
+pub(crate) fn poly_blamp(phase: f32, increment: f32) -> f32 {
+    let x = if phase < increment {
+        phase / increment
+    } else if phase > 1. - increment {
+        (phase - 1.) / increment
+    } else {
+        return 0.;
+    };
+    (1. - x.abs()).powi(3) / 6.
+}
+
+fn cycle_fraction(value: f32, increment: f32) -> (f32, f32) {
+    let increment = (increment.abs() / (2. * PI)).min(0.5);
+    (value / (2. * PI), increment.max(f32::EPSILON))
+}
+
+/// A sawtooth with the same shape as [`Sawtooth`](super::Sawtooth), but
+/// without the aliasing.
+#[derive(Debug)]
+pub struct BandLimitedSawtooth {}
+
+impl OscillatorFunction for BandLimitedSawtooth {
+    fn compute_sample(value: f32) -> f32 {
+        Self::compute_sample_with_increment(value, 0.)
+    }
+
+    fn compute_sample_with_increment(value: f32, increment: f32) -> f32 {
+        let (phase, increment) = cycle_fraction(value, increment);
+        // The naive sawtooth starts at 0 and resets halfway through its cycle
+        let phase = (phase + 0.5).fract();
+        2. * phase - 1. - poly_blep(phase, increment)
+    }
+}
+
+#[derive(Debug)]
+pub struct BandLimitedSquare {}
+
+impl OscillatorFunction for BandLimitedSquare {
+    fn compute_sample(value: f32) -> f32 {
+        Self::compute_sample_with_increment(value, 0.)
+    }
+
+    fn compute_sample_with_increment(value: f32, increment: f32) -> f32 {
+        let (phase, increment) = cycle_fraction(value, increment);
+        let naive = if phase < 0.5 { 1. } else { -1. };
+        naive + poly_blep(phase, increment) - poly_blep((phase + 0.5).fract(), increment)
+    }
+}
+
+/// A triangle ranging from -1 to 1, starting at its lowest point. Both
+/// triangles start at the same point in their cycle, but [`Triangle`]
+/// ranges from 0 to π, so switching between them changes the level and
+/// removes [`Triangle`]'s DC offset.
+///
+/// [`Triangle`]: super::Triangle
+#[derive(Debug)]
+pub struct BandLimitedTriangle {}
+
+impl OscillatorFunction for BandLimitedTriangle {
+    fn compute_sample(value: f32) -> f32 {
+        Self::compute_sample_with_increment(value, 0.)
+    }
+
+    fn compute_sample_with_increment(value: f32, increment: f32) -> f32 {
+        let (phase, increment) = cycle_fraction(value, increment);
+        let naive = 1. - 4. * (phase - 0.5).abs();
+        // The slope changes by 8 at each corner of the triangle
+        naive
+            + 8. * increment
+                * (poly_blamp(phase, increment) - poly_blamp((phase + 0.5).fract(), increment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::oscillator::{Sawtooth, Square};
+
+    #[test]
+    fn matches_naive_away_from_edges() {
+        let increment = 2. * PI * 440. / 44_100.;
+        for &value in &[0.5, 1.5, 2., 4., 5.5] {
+            assert!(
+                (BandLimitedSawtooth::compute_sample_with_increment(value, increment)
+                    - Sawtooth::compute_sample(value))
+                .abs()
+                    < 1e-5
+            );
+            assert_eq!(
+                BandLimitedSquare::compute_sample_with_increment(value, increment),
+                Square::compute_sample(value)
+            );
+        }
+        assert!(
+            (BandLimitedTriangle::compute_sample_with_increment(PI / 2., increment)).abs() < 1e-5
+        );
+    }
+
+    #[test]
+    fn smooths_discontinuities() {
+        let increment = 2. * PI * 4_410. / 44_100.;
+        // Just after the sawtooth resets, the naive version is at -1
+        let value = PI + increment / 2.;
+        let naive = Sawtooth::compute_sample(value);
+        let band_limited = BandLimitedSawtooth::compute_sample_with_increment(value, increment);
+        assert!(naive < -0.8);
+        assert!(band_limited > naive + 0.1);
+
+        // The band-limited triangle rounds off its lowest point
+        assert!(BandLimitedTriangle::compute_sample_with_increment(0., increment) > -1.);
+    }
+}
@@ -0,0 +1,50 @@
+use super::{band_limited::poly_blep, PhaseAccumulator, StartPhase};
+use crate::{
+    parameter::Parameter,
+    sampler::{FrameInfo, Sample, Sampler},
+};
+
+/// A band-limited pulse wave. `width` is the fraction of each cycle spent
+/// high, with 0.5 producing a square wave.
+#[derive(Debug)]
+pub struct Pulse {
+    frequency: Parameter,
+    amplitude: Parameter,
+    width: Parameter,
+    phase: PhaseAccumulator,
+}
+
+impl Pulse {
+    pub fn new(frequency: Parameter, amplitude: Parameter, width: Parameter) -> Self {
+        Self {
+            frequency,
+            amplitude,
+            width,
+            phase: PhaseAccumulator::new(StartPhase::Zero),
+        }
+    }
+
+    pub fn with_start_phase(mut self, start: StartPhase) -> Self {
+        self.phase = PhaseAccumulator::new(start);
+        self
+    }
+}
+
+impl Sampler for Pulse {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let frequency = self.frequency.next(frame)?;
+        let width = self.width.next(frame)?.clamp(0., 1.);
+        let amplitude = self.amplitude.next(frame)?;
+        let increment = (frequency.abs() / frame.sample_rate as f32).clamp(f32::EPSILON, 0.5);
+        let phase = self.phase.next(frequency, frame.sample_rate);
+
+        let naive = if phase < width { 1. } else { -1. };
+        let sample = naive + poly_blep(phase, increment)
+            - poly_blep((phase + 1. - width).fract(), increment);
+
+        Some(Sample {
+            left: amplitude * sample / 2.0,
+            right: amplitude * sample / 2.0,
+        })
+    }
+}
@@ -1,6 +1,10 @@
 use super::OscillatorFunction;
 use std::f32::consts::PI;
 
+/// A triangle ranging from 0 to π, starting at its lowest point. Unlike the
+/// other oscillators, it isn't centered on 0; see
+/// [`BandLimitedTriangle`](super::BandLimitedTriangle) for a triangle ranging
+/// from -1 to 1.
 #[derive(Debug)]
 pub struct Triangle {}
 
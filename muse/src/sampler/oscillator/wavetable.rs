@@ -0,0 +1,119 @@
+use super::{PhaseAccumulator, StartPhase};
+use crate::{
+    parameter::Parameter,
+    sampler::{FrameInfo, Sample, Sampler},
+    wav,
+};
+use std::{path::Path, sync::Arc};
+
+/// A set of single-cycle waveforms that a [`Wavetable`] morphs between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavetableData {
+    waves: Vec<Vec<f32>>,
+}
+
+impl WavetableData {
+    /// Creates a table from `waves`, ignoring any empty waves.
+    pub fn new(waves: Vec<Vec<f32>>) -> Self {
+        Self {
+            waves: waves.into_iter().filter(|wave| !wave.is_empty()).collect(),
+        }
+    }
+
+    /// Loads one wave per file. Each file should contain exactly one cycle,
+    /// and stereo files are mixed down to mono.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Self, wav::Error> {
+        let waves = paths
+            .iter()
+            .map(|path| Ok(wav::read(path)?.mono()))
+            .collect::<Result<_, wav::Error>>()?;
+        Ok(Self::new(waves))
+    }
+
+    /// Samples the table at `phase`, a fraction of a cycle, and `morph`, where
+    /// 0 is the first wave and 1 is the last.
+    pub fn value(&self, phase: f32, morph: f32) -> f32 {
+        if self.waves.is_empty() {
+            return 0.;
+        }
+
+        let position = morph.clamp(0., 1.) * (self.waves.len() - 1) as f32;
+        let first = position.floor() as usize;
+        let blend = position - first as f32;
+        let value = Self::wave_value(&self.waves[first], phase);
+        match self.waves.get(first + 1) {
+            Some(next) if blend > 0. => value + (Self::wave_value(next, phase) - value) * blend,
+            _ => value,
+        }
+    }
+
+    fn wave_value(wave: &[f32], phase: f32) -> f32 {
+        let position = phase * wave.len() as f32;
+        let index = position.floor() as usize % wave.len();
+        let next = (index + 1) % wave.len();
+        let blend = position.fract();
+        wave[index] + (wave[next] - wave[index]) * blend
+    }
+}
+
+#[derive(Debug)]
+pub struct Wavetable {
+    table: Arc<WavetableData>,
+    frequency: Parameter,
+    amplitude: Parameter,
+    morph: Parameter,
+    phase: PhaseAccumulator,
+}
+
+impl Wavetable {
+    pub fn new(
+        table: Arc<WavetableData>,
+        frequency: Parameter,
+        amplitude: Parameter,
+        morph: Parameter,
+    ) -> Self {
+        Self {
+            table,
+            frequency,
+            amplitude,
+            morph,
+            phase: PhaseAccumulator::new(StartPhase::Zero),
+        }
+    }
+
+    pub fn with_start_phase(mut self, start: StartPhase) -> Self {
+        self.phase = PhaseAccumulator::new(start);
+        self
+    }
+}
+
+impl Sampler for Wavetable {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let frequency = self.frequency.next(frame)?;
+        let morph = self.morph.next(frame)?;
+        let amplitude = self.amplitude.next(frame)?;
+        let phase = self.phase.next(frequency, frame.sample_rate);
+        let value = self.table.value(phase, morph);
+
+        Some(Sample {
+            left: amplitude * value / 2.0,
+            right: amplitude * value / 2.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolation() {
+        let table = WavetableData::new(vec![vec![0., 1., 0., -1.], vec![1., 1., -1., -1.]]);
+        assert_eq!(table.value(0.25, 0.), 1.);
+        assert_eq!(table.value(0.125, 0.), 0.5);
+        assert_eq!(table.value(0.875, 0.), -0.5);
+        assert_eq!(table.value(0., 0.5), 0.5);
+        assert_eq!(table.value(0., 1.), 1.);
+        assert_eq!(table.value(0., 2.), 1.);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    envelope::PlayingState,
+    instrument::ControlHandle,
+    parameter::Parameter,
+    sampler::{FrameInfo, Sample, Sampler},
+    wav::WavData,
+};
+use std::sync::Arc;
+
+/// A region of a sample, in frames, that repeats while a note is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serialization",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub struct LoopPoints {
+    pub start: usize,
+    /// The frame after the last frame of the loop.
+    pub end: usize,
+}
+
+/// Plays back decoded audio, repitched relative to `root_key`.
+///
+/// While the note is held, playback repeats between the loop points if there
+/// are any. Once released, the rest of a looped sample plays, unless it loops
+/// through the release. Samples without
+/// loop points stop when released, unless the amplitude has an envelope to
+/// release them. In one-shot mode the whole sample plays exactly once,
+/// regardless of when the note is released.
+#[derive(Debug)]
+pub struct SamplePlayer {
+    data: Arc<WavData>,
+    amplitude: Parameter,
+    /// Whether the amplitude fades the sample out once released.
+    amplitude_releases: bool,
+    root_key: u8,
+    loop_points: Option<LoopPoints>,
+    loop_through_release: bool,
+    one_shot: bool,
+    pitch_tracking: bool,
+    tune: f32,
+    position: f64,
+    control: ControlHandle,
+}
+
+impl SamplePlayer {
+    pub fn new(data: Arc<WavData>, amplitude: Parameter, control: ControlHandle) -> Self {
+        Self {
+            data,
+            amplitude_releases: amplitude.has_envelope(),
+            amplitude,
+            root_key: 60,
+            loop_points: None,
+            loop_through_release: false,
+            one_shot: false,
+            pitch_tracking: true,
+            tune: 0.,
+            position: 0.,
+            control,
+        }
+    }
+
+    /// The MIDI key that plays the sample at its recorded pitch.
+    pub fn with_root_key(mut self, root_key: u8) -> Self {
+        self.root_key = root_key;
+        self
+    }
+
+    pub fn with_loop(mut self, loop_points: Option<LoopPoints>) -> Self {
+        self.loop_points = loop_points
+            .filter(|points| points.start < points.end && points.end <= self.data.samples.len());
+        self
+    }
+
+    /// Keeps looping once the note is released, for as long as the amplitude
+    /// lasts.
+    pub fn loop_through_release(mut self, loop_through_release: bool) -> Self {
+        self.loop_through_release = loop_through_release;
+        self
+    }
+
+    pub fn one_shot(mut self, one_shot: bool) -> Self {
+        self.one_shot = one_shot;
+        self
+    }
+
+    /// When disabled, the sample plays at its recorded pitch for every note.
+    pub fn pitch_tracking(mut self, pitch_tracking: bool) -> Self {
+        self.pitch_tracking = pitch_tracking;
+        self
+    }
+
+    /// Offsets the playback pitch by `cents`.
+    pub fn with_tuning(mut self, cents: f32) -> Self {
+        self.tune = cents;
+        self
+    }
+
+    fn is_held(&self) -> bool {
+        matches!(
+            self.control.load(),
+            PlayingState::Playing | PlayingState::Sustaining
+        )
+    }
+
+    fn is_looping(&self) -> bool {
+        !self.one_shot && (self.loop_through_release || self.is_held())
+    }
+
+    fn stop(&mut self) -> Option<Sample> {
+        self.control.store(PlayingState::Stopped);
+        None
+    }
+
+    fn interpolated(&self, position: f64) -> Option<Sample> {
+        let index = position.floor() as usize;
+        let current = *self.data.samples.get(index)?;
+        let next = match (self.loop_points, self.data.samples.get(index + 1)) {
+            (Some(points), _) if index + 1 == points.end && self.is_looping() => {
+                self.data.samples[points.start]
+            }
+            (_, Some(next)) => *next,
+            (_, None) => current,
+        };
+        let blend = position.fract() as f32;
+        Some(current * (1. - blend) + next * blend)
+    }
+}
+
+impl Sampler for SamplePlayer {
+    fn sample(&mut self, frame: &FrameInfo) -> Option<Sample> {
+        let amplitude = match self.amplitude.next(frame) {
+            Some(amplitude) => amplitude,
+            None => return self.stop(),
+        };
+
+        if self.loop_points.is_none()
+            && !self.one_shot
+            && !self.amplitude_releases
+            && !self.is_held()
+        {
+            return self.stop();
+        }
+
+        let sample = match self.interpolated(self.position) {
+            Some(sample) => sample,
+            None => return self.stop(),
+        };
+
+        let mut rate = self.data.sample_rate as f64 / frame.sample_rate as f64
+            * 2f64.powf(self.tune as f64 / 1200.);
+        if self.pitch_tracking {
+            let root_hertz = pitch_calc::hz_from_step(self.root_key as f32);
+            rate *= (frame.note.hertz() / root_hertz) as f64;
+        }
+        self.position += rate;
+
+        if let Some(points) = self.loop_points {
+            if self.is_looping() && self.position >= points.end as f64 {
+                let length = (points.end - points.start) as f64;
+                self.position =
+                    points.start as f64 + (self.position - points.start as f64) % length;
+            }
+        }
+
+        Some(sample * amplitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Note;
+    use crossbeam::atomic::AtomicCell;
+
+    fn ramp() -> Arc<WavData> {
+        Arc::new(WavData {
+            sample_rate: 100,
+            samples: (0..10)
+                .map(|value| Sample {
+                    left: value as f32,
+                    right: value as f32,
+                })
+                .collect(),
+        })
+    }
+
+    fn render(player: &mut SamplePlayer, note: Note, frames: usize) -> Vec<f32> {
+        let frame = FrameInfo {
+            clock: 0,
+            sample_rate: 100,
+            note,
+        };
+        (0..frames)
+            .map_while(|_| player.sample(&frame).map(|sample| sample.left))
+            .collect()
+    }
+
+    #[test]
+    fn pitch_tracking() {
+        let control = Arc::new(AtomicCell::new(PlayingState::Playing));
+        let mut player = SamplePlayer::new(ramp(), Parameter::Value(1.), control.clone());
+        let octave_up = Note::new(72., 127);
+        let values = render(&mut player, octave_up, 10);
+        assert_eq!(values.len(), 5);
+        assert!((values[1] - 2.).abs() < 0.01);
+        assert_eq!(control.load(), PlayingState::Stopped);
+    }
+
+    #[test]
+    fn loops_until_released() {
+        let control = Arc::new(AtomicCell::new(PlayingState::Playing));
+        let mut player = SamplePlayer::new(ramp(), Parameter::Value(1.), control.clone())
+            .with_loop(Some(LoopPoints { start: 2, end: 4 }))
+            .pitch_tracking(false);
+        let values = render(&mut player, Note::default(), 8);
+        assert_eq!(values, vec![0., 1., 2., 3., 2., 3., 2., 3.]);
+
+        control.store(PlayingState::Stopping);
+        let values = render(&mut player, Note::default(), 10);
+        assert_eq!(values, vec![2., 3., 4., 5., 6., 7., 8., 9.]);
+        assert_eq!(control.load(), PlayingState::Stopped);
+    }
+
+    #[test]
+    fn loops_through_release() {
+        let control = Arc::new(AtomicCell::new(PlayingState::Playing));
+        let mut player = SamplePlayer::new(ramp(), Parameter::Value(1.), control.clone())
+            .with_loop(Some(LoopPoints { start: 2, end: 4 }))
+            .loop_through_release(true)
+            .pitch_tracking(false);
+        assert_eq!(
+            render(&mut player, Note::default(), 4),
+            vec![0., 1., 2., 3.]
+        );
+
+        control.store(PlayingState::Stopping);
+        let values = render(&mut player, Note::default(), 20);
+        assert_eq!(values.len(), 20);
+        assert!(values.iter().all(|&value| value == 2. || value == 3.));
+    }
+
+    #[test]
+    fn unlooped_samples_stop_when_released() {
+        let control = Arc::new(AtomicCell::new(PlayingState::Playing));
+        let mut player =
+            SamplePlayer::new(ramp(), Parameter::Value(1.), control.clone()).pitch_tracking(false);
+        assert_eq!(render(&mut player, Note::default(), 3), vec![0., 1., 2.]);
+
+        control.store(PlayingState::Stopping);
+        assert!(render(&mut player, Note::default(), 3).is_empty());
+        assert_eq!(control.load(), PlayingState::Stopped);
+    }
+
+    #[test]
+    fn one_shot_ignores_loop() {
+        let control = Arc::new(AtomicCell::new(PlayingState::Playing));
+        let mut player = SamplePlayer::new(ramp(), Parameter::Value(1.), control)
+            .with_loop(Some(LoopPoints { start: 2, end: 4 }))
+            .one_shot(true)
+            .pitch_tracking(false);
+        assert_eq!(render(&mut player, Note::default(), 20).len(), 10);
+    }
+}
@@ -0,0 +1,715 @@
+//! Loads SoundFont 2 files, turning their presets into [`LoadedInstrument`]s
+//! that can be played by a [`VirtualInstrument`](crate::instrument::VirtualInstrument).
+//!
+//! Sample data, key and velocity zones, volume envelopes, and the tuning, pan,
+//! attenuation, sample offset and loop generators are supported. Modulators,
+//! filters, LFOs and the modulation envelope are ignored.
+
+use crate::{
+    envelope::{EnvelopeBuilder, EnvelopeCurve, EnvelopeScaling, StageScaling},
+    node::{LoadedInstrument, Node, Parameter, Zone},
+    sampler::{LoopPoints, Sample},
+    wav::WavData,
+};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("error reading soundfont: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid soundfont: {0}")]
+    InvalidFormat(&'static str),
+    #[error("no preset with bank {bank} and program {program}")]
+    PresetNotFound { bank: u16, program: u16 },
+    #[error("error building envelope: {0}")]
+    Envelope(#[from] crate::envelope::EnvelopeCurveError),
+}
+
+/// The generators this loader understands, as numbered by the SoundFont 2.04
+/// specification.
+mod generator {
+    pub const START_OFFSET: u16 = 0;
+    pub const END_OFFSET: u16 = 1;
+    pub const START_LOOP_OFFSET: u16 = 2;
+    pub const END_LOOP_OFFSET: u16 = 3;
+    pub const START_COARSE_OFFSET: u16 = 4;
+    pub const END_COARSE_OFFSET: u16 = 12;
+    pub const PAN: u16 = 17;
+    pub const ATTACK_VOLUME: u16 = 34;
+    pub const HOLD_VOLUME: u16 = 35;
+    pub const DECAY_VOLUME: u16 = 36;
+    pub const SUSTAIN_VOLUME: u16 = 37;
+    pub const RELEASE_VOLUME: u16 = 38;
+    pub const KEY_TO_HOLD_VOLUME: u16 = 39;
+    pub const KEY_TO_DECAY_VOLUME: u16 = 40;
+    pub const INSTRUMENT: u16 = 41;
+    pub const KEY_RANGE: u16 = 43;
+    pub const VELOCITY_RANGE: u16 = 44;
+    pub const START_LOOP_COARSE_OFFSET: u16 = 45;
+    pub const ATTENUATION: u16 = 48;
+    pub const END_LOOP_COARSE_OFFSET: u16 = 50;
+    pub const COARSE_TUNE: u16 = 51;
+    pub const FINE_TUNE: u16 = 52;
+    pub const SAMPLE_ID: u16 = 53;
+    pub const SAMPLE_MODES: u16 = 54;
+    pub const SCALE_TUNING: u16 = 56;
+    pub const OVERRIDING_ROOT_KEY: u16 = 58;
+
+    pub fn default_value(generator: u16) -> i16 {
+        match generator {
+            ATTACK_VOLUME | HOLD_VOLUME | DECAY_VOLUME | RELEASE_VOLUME => -12000,
+            SCALE_TUNING => 100,
+            OVERRIDING_ROOT_KEY => -1,
+            _ => 0,
+        }
+    }
+}
+
+/// The name and number of a preset in a [`SoundFont`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetInfo {
+    pub name: String,
+    pub bank: u16,
+    pub program: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Generators(HashMap<u16, [u8; 2]>);
+
+impl Generators {
+    fn value(&self, generator: u16) -> Option<i16> {
+        self.0
+            .get(&generator)
+            .map(|amount| i16::from_le_bytes(*amount))
+    }
+
+    fn range(&self, generator: u16) -> (u8, u8) {
+        self.0
+            .get(&generator)
+            .map(|amount| (amount[0], amount[1]))
+            .unwrap_or((0, 127))
+    }
+
+    /// Layers `zone` over the global zone's generators.
+    fn with_globals(global: Option<&Generators>, zone: &Generators) -> Generators {
+        let mut combined = global.cloned().unwrap_or_default();
+        combined.0.extend(zone.0.iter().map(|(k, v)| (*k, *v)));
+        combined
+    }
+}
+
+#[derive(Debug)]
+struct Preset {
+    info: PresetInfo,
+    global: Option<Generators>,
+    zones: Vec<Generators>,
+}
+
+#[derive(Debug)]
+struct Instrument {
+    global: Option<Generators>,
+    zones: Vec<Generators>,
+}
+
+#[derive(Debug)]
+struct SampleHeader {
+    start: u32,
+    end: u32,
+    loop_start: u32,
+    loop_end: u32,
+    sample_rate: u32,
+    original_pitch: u8,
+    pitch_correction: i8,
+}
+
+/// A parsed SoundFont 2 file.
+#[derive(Debug)]
+pub struct SoundFont {
+    presets: Vec<Preset>,
+    instruments: Vec<Instrument>,
+    samples: Vec<SampleHeader>,
+    sample_data: Vec<i16>,
+    decoded: Mutex<HashMap<(usize, u32, u32), Arc<WavData>>>,
+}
+
+impl SoundFont {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (id, body) = chunks(bytes)
+            .next()
+            .ok_or(Error::InvalidFormat("missing RIFF chunk"))?;
+        if &id != b"RIFF" || body.get(0..4) != Some(b"sfbk") {
+            return Err(Error::InvalidFormat("not a soundfont"));
+        }
+
+        let mut sample_data = None;
+        let mut pdta = HashMap::new();
+        for (id, list) in chunks(&body[4..]) {
+            if &id != b"LIST" || list.len() < 4 {
+                continue;
+            }
+            for (id, chunk) in chunks(&list[4..]) {
+                match (&list[0..4], &id) {
+                    (b"sdta", b"smpl") => {
+                        sample_data = Some(
+                            chunk
+                                .chunks_exact(2)
+                                .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+                                .collect::<Vec<_>>(),
+                        )
+                    }
+                    (b"pdta", _) => {
+                        pdta.insert(id, chunk);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let sample_data = sample_data.ok_or(Error::InvalidFormat("missing sample data"))?;
+        let hydra = |id: &[u8; 4], record_size: usize| -> Result<Vec<&[u8]>, Error> {
+            let chunk = pdta
+                .get(id)
+                .ok_or(Error::InvalidFormat("missing preset data"))?;
+            Ok(chunk.chunks_exact(record_size).collect())
+        };
+
+        let preset_headers = hydra(b"phdr", 38)?;
+        let preset_bags = hydra(b"pbag", 4)?;
+        let preset_generators = hydra(b"pgen", 4)?;
+        let instrument_headers = hydra(b"inst", 22)?;
+        let instrument_bags = hydra(b"ibag", 4)?;
+        let instrument_generators = hydra(b"igen", 4)?;
+        let sample_headers = hydra(b"shdr", 46)?;
+
+        // The last record of each list only marks where the previous one ends
+        let presets = preset_headers
+            .windows(2)
+            .map(|headers| {
+                let info = PresetInfo {
+                    name: name(&headers[0][0..20]),
+                    program: u16_at(headers[0], 20),
+                    bank: u16_at(headers[0], 22),
+                };
+                let (global, zones) = zones(
+                    &preset_bags,
+                    &preset_generators,
+                    u16_at(headers[0], 24),
+                    u16_at(headers[1], 24),
+                    generator::INSTRUMENT,
+                )?;
+                Ok(Preset {
+                    info,
+                    global,
+                    zones,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        let instruments = instrument_headers
+            .windows(2)
+            .map(|headers| {
+                let (global, zones) = zones(
+                    &instrument_bags,
+                    &instrument_generators,
+                    u16_at(headers[0], 20),
+                    u16_at(headers[1], 20),
+                    generator::SAMPLE_ID,
+                )?;
+                Ok(Instrument { global, zones })
+            })
+            .collect::<Result<_, Error>>()?;
+        let samples = sample_headers
+            .iter()
+            .take(sample_headers.len().saturating_sub(1))
+            .map(|header| SampleHeader {
+                start: u32_at(header, 20),
+                end: u32_at(header, 24),
+                loop_start: u32_at(header, 28),
+                loop_end: u32_at(header, 32),
+                sample_rate: u32_at(header, 36),
+                original_pitch: header[40],
+                pitch_correction: header[41] as i8,
+            })
+            .collect();
+
+        Ok(Self {
+            presets,
+            instruments,
+            samples,
+            sample_data,
+            decoded: Mutex::default(),
+        })
+    }
+
+    pub fn presets(&self) -> impl Iterator<Item = &PresetInfo> {
+        self.presets.iter().map(|preset| &preset.info)
+    }
+
+    /// Builds an instrument from the preset with the matching bank and
+    /// program numbers.
+    pub fn preset(&self, bank: u16, program: u16) -> Result<LoadedInstrument, Error> {
+        let preset = self
+            .presets
+            .iter()
+            .find(|preset| preset.info.bank == bank && preset.info.program == program)
+            .ok_or(Error::PresetNotFound { bank, program })?;
+        self.build_preset(preset)
+    }
+
+    fn build_preset(&self, preset: &Preset) -> Result<LoadedInstrument, Error> {
+        let mut zones = Vec::new();
+        for preset_zone in &preset.zones {
+            let preset_zone = Generators::with_globals(preset.global.as_ref(), preset_zone);
+            let instrument = preset_zone
+                .value(generator::INSTRUMENT)
+                .and_then(|index| self.instruments.get(index as usize))
+                .ok_or(Error::InvalidFormat(
+                    "preset zone refers to a missing instrument",
+                ))?;
+
+            for instrument_zone in &instrument.zones {
+                let instrument_zone =
+                    Generators::with_globals(instrument.global.as_ref(), instrument_zone);
+                if let Some(zone) = self.build_zone(&preset_zone, &instrument_zone)? {
+                    zones.push(zone);
+                }
+            }
+        }
+
+        Ok(LoadedInstrument::new(Node::Amplify {
+            value: Parameter::NoteVelocity,
+            input: Box::new(Node::zoned(zones)),
+        }))
+    }
+
+    fn build_zone(
+        &self,
+        preset: &Generators,
+        instrument: &Generators,
+    ) -> Result<Option<Zone<()>>, Error> {
+        let (keys, velocities) = match (
+            intersect(
+                preset.range(generator::KEY_RANGE),
+                instrument.range(generator::KEY_RANGE),
+            ),
+            intersect(
+                preset.range(generator::VELOCITY_RANGE),
+                instrument.range(generator::VELOCITY_RANGE),
+            ),
+        ) {
+            (Some(keys), Some(velocities)) => (keys, velocities),
+            _ => return Ok(None),
+        };
+
+        // Preset generators offset the instrument's values
+        let value = |generator: u16| -> i32 {
+            instrument
+                .value(generator)
+                .unwrap_or_else(|| generator::default_value(generator)) as i32
+                + preset.value(generator).unwrap_or_default() as i32
+        };
+        let offset = |fine: u16, coarse: u16| -> i64 {
+            instrument.value(fine).unwrap_or_default() as i64
+                + instrument.value(coarse).unwrap_or_default() as i64 * 32768
+        };
+
+        let sample_index = instrument
+            .value(generator::SAMPLE_ID)
+            .ok_or(Error::InvalidFormat("instrument zone has no sample"))?
+            as u16 as usize;
+        let header = self.samples.get(sample_index).ok_or(Error::InvalidFormat(
+            "instrument zone refers to a missing sample",
+        ))?;
+        let start = (header.start as i64
+            + offset(generator::START_OFFSET, generator::START_COARSE_OFFSET))
+        .clamp(0, self.sample_data.len() as i64) as u32;
+        let end = (header.end as i64 + offset(generator::END_OFFSET, generator::END_COARSE_OFFSET))
+            .clamp(start as i64, self.sample_data.len() as i64) as u32;
+        let data = self.decode(sample_index, start, end, header.sample_rate);
+
+        // Modes 1 and 3 loop, and the loop end is the first frame after the
+        // loop. Mode 1 keeps looping through the release, while mode 3 plays
+        // the rest of the sample once released.
+        let sample_mode = value(generator::SAMPLE_MODES);
+        let loop_points = match sample_mode & 1 {
+            1 => {
+                let loop_start = header.loop_start as i64
+                    + offset(
+                        generator::START_LOOP_OFFSET,
+                        generator::START_LOOP_COARSE_OFFSET,
+                    );
+                let loop_end = header.loop_end as i64
+                    + offset(
+                        generator::END_LOOP_OFFSET,
+                        generator::END_LOOP_COARSE_OFFSET,
+                    );
+                Some(LoopPoints {
+                    start: (loop_start - start as i64).max(0) as usize,
+                    end: (loop_end - start as i64).max(0) as usize,
+                })
+            }
+            _ => None,
+        };
+
+        let root_key = match value(generator::OVERRIDING_ROOT_KEY) {
+            key @ 0..=127 => key as u8,
+            _ if header.original_pitch <= 127 => header.original_pitch,
+            _ => 60,
+        };
+        let tune = (value(generator::COARSE_TUNE) * 100
+            + value(generator::FINE_TUNE)
+            + header.pitch_correction as i32) as f32;
+
+        let envelope = EnvelopeBuilder::default()
+            .attack(EnvelopeCurve::Timed(timecents(value(
+                generator::ATTACK_VOLUME,
+            ))))
+            .hold(EnvelopeCurve::Timed(timecents(value(
+                generator::HOLD_VOLUME,
+            ))))
+            .decay(EnvelopeCurve::Timed(timecents(value(
+                generator::DECAY_VOLUME,
+            ))))
+            .sustain(EnvelopeCurve::Sustain(centibels(value(
+                generator::SUSTAIN_VOLUME,
+            ))))
+            .release(EnvelopeCurve::Timed(timecents(value(
+                generator::RELEASE_VOLUME,
+            ))))
+            .scaling(
+                EnvelopeScaling::default()
+                    .hold(
+                        StageScaling::default()
+                            .key(key_scaling(value(generator::KEY_TO_HOLD_VOLUME))),
+                    )
+                    .decay(
+                        StageScaling::default()
+                            .key(key_scaling(value(generator::KEY_TO_DECAY_VOLUME))),
+                    ),
+            )
+            .build()?;
+
+        let mut node = Node::Sample {
+            data,
+            root_key,
+            amplitude: Parameter::Envelope(envelope),
+            loop_points,
+            loop_through_release: sample_mode & 3 == 1,
+            one_shot: false,
+            pitch_tracking: value(generator::SCALE_TUNING) != 0,
+            tune,
+        };
+
+        // Pan is in tenths of a percent, from -500 (left) to 500 (right). It
+        // is applied even when centered so that every zone has the same level.
+        let pan = value(generator::PAN).clamp(-500, 500);
+        node = Node::Pan {
+            value: Parameter::Value((pan + 500) as f32 / 1000.),
+            input: Box::new(node),
+        };
+        let attenuation = value(generator::ATTENUATION);
+        if attenuation > 0 {
+            node = Node::Amplify {
+                value: Parameter::Value(centibels(attenuation)),
+                input: Box::new(node),
+            };
+        }
+
+        Ok(Some(Zone {
+            keys: keys.0..=keys.1,
+            velocities: velocities.0..=velocities.1,
+            round_robin: None,
+            node,
+        }))
+    }
+
+    fn decode(&self, sample_index: usize, start: u32, end: u32, sample_rate: u32) -> Arc<WavData> {
+        let mut decoded = self.decoded.lock().expect("Error locking decoded samples");
+        decoded
+            .entry((sample_index, start, end))
+            .or_insert_with(|| {
+                Arc::new(WavData {
+                    sample_rate,
+                    samples: self.sample_data[start as usize..end as usize]
+                        .iter()
+                        .map(|&value| {
+                            let value = value as f32 / 32768.;
+                            Sample {
+                                left: value,
+                                right: value,
+                            }
+                        })
+                        .collect(),
+                })
+            })
+            .clone()
+    }
+}
+
+/// Splits RIFF data into its chunks' ids and contents.
+fn chunks(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let id: [u8; 4] = data.get(0..4)?.try_into().ok()?;
+        let length = u32_at(data.get(0..8)?, 4) as usize;
+        let body = data.get(8..8 + length)?;
+        // Chunks are padded to an even length
+        data = data.get(8 + length + length % 2..).unwrap_or_default();
+        Some((id, body))
+    })
+}
+
+/// Reads a bag's generators, separating out the global zone. The global zone
+/// is the first zone, when it doesn't end with `terminal_generator`.
+fn zones(
+    bags: &[&[u8]],
+    generators: &[&[u8]],
+    first_bag: u16,
+    end_bag: u16,
+    terminal_generator: u16,
+) -> Result<(Option<Generators>, Vec<Generators>), Error> {
+    let mut global = None;
+    let mut zones = Vec::new();
+    for bag in first_bag..end_bag {
+        let first_generator = bags
+            .get(bag as usize)
+            .map(|bag| u16_at(bag, 0))
+            .ok_or(Error::InvalidFormat("missing bag"))?;
+        let end_generator = bags
+            .get(bag as usize + 1)
+            .map(|bag| u16_at(bag, 0))
+            .ok_or(Error::InvalidFormat("missing bag"))?;
+        let zone = Generators(
+            generators
+                .get(first_generator as usize..end_generator as usize)
+                .ok_or(Error::InvalidFormat("missing generators"))?
+                .iter()
+                .map(|generator| (u16_at(generator, 0), [generator[2], generator[3]]))
+                .collect(),
+        );
+
+        if zone.0.contains_key(&terminal_generator) {
+            zones.push(zone);
+        } else if bag == first_bag {
+            global = Some(zone);
+        }
+    }
+    Ok((global, zones))
+}
+
+fn intersect(a: (u8, u8), b: (u8, u8)) -> Option<(u8, u8)> {
+    let range = (a.0.max(b.0), a.1.min(b.1));
+    if range.0 <= range.1 {
+        Some(range)
+    } else {
+        None
+    }
+}
+
+fn timecents(value: i32) -> Duration {
+    Duration::from_secs_f32(2f32.powf(value as f32 / 1200.))
+}
+
+/// Converts timecents per key, which shorten a stage as keys rise above 60,
+/// into the per-octave scaling used by [`StageScaling::key`].
+fn key_scaling(timecents_per_key: i32) -> f32 {
+    2f32.powf(-timecents_per_key as f32 / 100.) - 1.
+}
+
+fn centibels(attenuation: i32) -> f32 {
+    10f32.powf(-(attenuation.max(0) as f32) / 200.)
+}
+
+fn name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_owned()
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instrument::ControlHandles,
+        node::Instantiatable,
+        sampler::{FrameInfo, Sampler},
+        Note,
+    };
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn list(kind: &[u8; 4], chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = kind.to_vec();
+        chunks.iter().for_each(|c| body.extend_from_slice(c));
+        chunk(b"LIST", &body)
+    }
+
+    fn record(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    fn named(name: &str) -> [u8; 20] {
+        let mut bytes = [0; 20];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        bytes
+    }
+
+    fn generator(operator: u16, amount: [u8; 2]) -> Vec<u8> {
+        record(&[&operator.to_le_bytes(), &amount])
+    }
+
+    /// A soundfont with one preset playing a constant sample on keys 60-72,
+    /// tuned up an octave and attenuated by 6dB.
+    fn tiny_soundfont() -> Vec<u8> {
+        let samples = [16384i16; 100]
+            .iter()
+            .flat_map(|value| value.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let phdr = [
+            record(&[
+                &named("Tiny"),
+                &3u16.to_le_bytes(),
+                &1u16.to_le_bytes(),
+                &0u16.to_le_bytes(),
+                &[0; 12],
+            ]),
+            record(&[&named("EOP"), &[0; 4], &2u16.to_le_bytes(), &[0; 12]]),
+        ]
+        .concat();
+        // A global zone followed by a zone referencing the instrument
+        let pbag = [
+            record(&[&0u16.to_le_bytes(), &0u16.to_le_bytes()]),
+            record(&[&1u16.to_le_bytes(), &0u16.to_le_bytes()]),
+            record(&[&2u16.to_le_bytes(), &0u16.to_le_bytes()]),
+        ]
+        .concat();
+        let pgen = [
+            generator(generator::COARSE_TUNE, 12i16.to_le_bytes()),
+            generator(generator::INSTRUMENT, 0i16.to_le_bytes()),
+            generator(0, [0, 0]),
+        ]
+        .concat();
+        let inst = [
+            record(&[&named("Instrument"), &0u16.to_le_bytes()]),
+            record(&[&named("EOI"), &1u16.to_le_bytes()]),
+        ]
+        .concat();
+        let ibag = [
+            record(&[&0u16.to_le_bytes(), &0u16.to_le_bytes()]),
+            record(&[&4u16.to_le_bytes(), &0u16.to_le_bytes()]),
+        ]
+        .concat();
+        let igen = [
+            generator(generator::KEY_RANGE, [60, 72]),
+            generator(generator::ATTENUATION, 60i16.to_le_bytes()),
+            generator(generator::SAMPLE_MODES, 1i16.to_le_bytes()),
+            generator(generator::SAMPLE_ID, 0i16.to_le_bytes()),
+            generator(0, [0, 0]),
+        ]
+        .concat();
+        let shdr = [
+            record(&[
+                &named("Sample"),
+                &0u32.to_le_bytes(),
+                &100u32.to_le_bytes(),
+                &10u32.to_le_bytes(),
+                &90u32.to_le_bytes(),
+                &44_100u32.to_le_bytes(),
+                &[60, 0],
+                &[0; 4],
+            ]),
+            record(&[&named("EOS"), &[0; 26]]),
+        ]
+        .concat();
+
+        let mut body = b"sfbk".to_vec();
+        body.extend(list(b"INFO", &[chunk(b"ifil", &[2, 0, 4, 0])]));
+        body.extend(list(b"sdta", &[chunk(b"smpl", &samples)]));
+        body.extend(list(
+            b"pdta",
+            &[
+                chunk(b"phdr", &phdr),
+                chunk(b"pbag", &pbag),
+                chunk(b"pmod", &[0; 10]),
+                chunk(b"pgen", &pgen),
+                chunk(b"inst", &inst),
+                chunk(b"ibag", &ibag),
+                chunk(b"imod", &[0; 10]),
+                chunk(b"igen", &igen),
+                chunk(b"shdr", &shdr),
+            ],
+        ));
+        chunk(b"RIFF", &body)
+    }
+
+    #[test]
+    fn loads_presets() {
+        let soundfont = SoundFont::from_bytes(&tiny_soundfont()).unwrap();
+        assert_eq!(
+            soundfont.presets().collect::<Vec<_>>(),
+            vec![&PresetInfo {
+                name: "Tiny".to_owned(),
+                bank: 1,
+                program: 3,
+            }]
+        );
+        assert!(matches!(
+            soundfont.preset(0, 3),
+            Err(Error::PresetNotFound {
+                bank: 0,
+                program: 3
+            })
+        ));
+
+        let instrument = soundfont.preset(1, 3).unwrap();
+        let play = |step: f32| {
+            let note = Note::new(step, 127);
+            let mut sampler = instrument.instantiate(&note, &ControlHandles::new());
+            (0..1_000)
+                .filter_map(|clock| {
+                    sampler.sample(&FrameInfo {
+                        clock,
+                        sample_rate: 44_100,
+                        note,
+                    })
+                })
+                .last()
+                .map(|sample| sample.left)
+        };
+
+        assert_eq!(play(48.), None);
+        // The sample loops while held, at half volume after the attenuation
+        // and half again from the centered pan
+        let value = play(60.).unwrap();
+        assert!((value - 0.125).abs() < 0.01, "{}", value);
+    }
+}
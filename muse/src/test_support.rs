@@ -0,0 +1,66 @@
+use crate::{
+    sampler::Sample,
+    wav::{self, Channels, WavFormat, WavSpec},
+};
+use std::path::{Path, PathBuf};
+
+/// A directory in the system's temp dir for a test's files. It is removed when
+/// dropped, including when the test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("muse-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+
+    /// Writes `contents` to `name`, creating any directories it's in.
+    pub fn write<P: AsRef<Path>>(&self, name: P, contents: &str) -> PathBuf {
+        let path = self.create_parent(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Writes `values` to `name` as a mono float WAV file, creating any
+    /// directories it's in.
+    pub fn write_wav<P: AsRef<Path>>(&self, name: P, sample_rate: u32, values: &[f32]) -> PathBuf {
+        let path = self.create_parent(name);
+        let samples = values
+            .iter()
+            .map(|&value| Sample {
+                left: value,
+                right: value,
+            })
+            .collect::<Vec<_>>();
+        wav::write(
+            &path,
+            WavSpec::new(sample_rate, Channels::Mono, WavFormat::Float32),
+            &samples,
+        )
+        .unwrap();
+        path
+    }
+
+    fn create_parent<P: AsRef<Path>>(&self, name: P) -> PathBuf {
+        let path = self.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).unwrap_or_default();
+    }
+}
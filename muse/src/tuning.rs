@@ -0,0 +1,443 @@
+use std::{fmt::Debug, path::Path};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("error reading file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("error parsing line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("key {0} is not mapped by the tuning")]
+    UnmappedKey(u8),
+}
+
+/// Maps MIDI keys to frequencies.
+pub trait Tuning: Send + Sync + Debug {
+    /// The frequency of `key` in hertz, or `None` if the tuning leaves the key
+    /// unmapped.
+    fn hertz(&self, key: u8) -> Option<f32>;
+}
+
+/// Divides each octave into `divisions` equal steps, with one step per key.
+/// Zero divisions leaves every key unmapped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqualDivision {
+    pub divisions: u32,
+    pub reference_key: u8,
+    pub reference_hertz: f32,
+}
+
+impl Default for EqualDivision {
+    /// Twelve-tone equal temperament with A4 = 440Hz.
+    fn default() -> Self {
+        Self::new(12)
+    }
+}
+
+impl EqualDivision {
+    /// Divides the octave into `divisions` steps, with key 69 at 440Hz.
+    pub fn new(divisions: u32) -> Self {
+        Self {
+            divisions,
+            reference_key: 69,
+            reference_hertz: 440.,
+        }
+    }
+
+    pub fn with_reference(mut self, key: u8, hertz: f32) -> Self {
+        self.reference_key = key;
+        self.reference_hertz = hertz;
+        self
+    }
+}
+
+impl Tuning for EqualDivision {
+    fn hertz(&self, key: u8) -> Option<f32> {
+        if self.divisions == 0 {
+            return None;
+        }
+        let steps = key as f32 - self.reference_key as f32;
+        Some(self.reference_hertz * 2f32.powf(steps / self.divisions as f32))
+    }
+}
+
+/// Tunes each key by a frequency ratio from the nearest root below it. The
+/// ratios cover one octave starting with the root. Without any ratios, every
+/// key is unmapped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JustIntonation {
+    pub ratios: Vec<f32>,
+    pub root_key: u8,
+    pub root_hertz: f32,
+}
+
+impl JustIntonation {
+    pub fn new(ratios: Vec<f32>, root_key: u8, root_hertz: f32) -> Self {
+        Self {
+            ratios,
+            root_key,
+            root_hertz,
+        }
+    }
+
+    /// The common five-limit chromatic scale, rooted at `root_key`.
+    pub fn five_limit(root_key: u8, root_hertz: f32) -> Self {
+        Self::new(
+            vec![
+                1.,
+                16. / 15.,
+                9. / 8.,
+                6. / 5.,
+                5. / 4.,
+                4. / 3.,
+                45. / 32.,
+                3. / 2.,
+                8. / 5.,
+                5. / 3.,
+                9. / 5.,
+                15. / 8.,
+            ],
+            root_key,
+            root_hertz,
+        )
+    }
+}
+
+impl Tuning for JustIntonation {
+    fn hertz(&self, key: u8) -> Option<f32> {
+        let steps = key as i32 - self.root_key as i32;
+        let length = self.ratios.len() as i32;
+        if length == 0 {
+            return None;
+        }
+        let ratio = self.ratios.get(steps.rem_euclid(length) as usize)?;
+        Some(self.root_hertz * ratio * 2f32.powi(steps.div_euclid(length)))
+    }
+}
+
+/// A scale read from a Scala `.scl` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaScale {
+    pub description: String,
+    /// The ratio of each degree to the scale's root, excluding the root
+    /// itself. The last ratio is the period the scale repeats at, which is
+    /// usually an octave.
+    pub ratios: Vec<f64>,
+}
+
+impl ScalaScale {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut lines = data_lines(source);
+        let description = lines.next().map(|(_, line)| line).unwrap_or_default();
+        let (count_line, count) = next_line(&mut lines, "note count")?;
+        let count = parse_word::<usize>(count_line, count, "note count")?;
+        if count == 0 {
+            return Err(parse_error(count_line, "scale has no pitches"));
+        }
+
+        let ratios = lines
+            .filter(|(_, text)| !text.is_empty())
+            .take(count)
+            .map(|(line, text)| parse_pitch(text).ok_or_else(|| parse_error(line, "invalid pitch")))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ratios.len() != count {
+            return Err(parse_error(
+                0,
+                "scale has fewer pitches than its note count",
+            ));
+        }
+
+        Ok(Self {
+            description: description.to_owned(),
+            ratios,
+        })
+    }
+
+    /// The ratio of `degree` to the root, which may be in any period, or
+    /// `None` if the scale has no pitches.
+    pub fn ratio(&self, degree: i32) -> Option<f64> {
+        let length = self.ratios.len() as i32;
+        let period = *self.ratios.last()?;
+        let within = degree.rem_euclid(length);
+        let ratio = if within == 0 {
+            1.
+        } else {
+            self.ratios[within as usize - 1]
+        };
+        Some(ratio * period.powi(degree.div_euclid(length)))
+    }
+}
+
+/// Assigns scale degrees to keys, read from a Scala `.kbm` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_key: u8,
+    pub last_key: u8,
+    /// The key that plays the scale's root.
+    pub middle_key: u8,
+    pub reference_key: u8,
+    pub reference_hertz: f64,
+    /// The scale degree that each repetition of `degrees` advances by.
+    pub octave_degree: i32,
+    /// The scale degree of each key from the middle key onwards, repeating.
+    /// Keys mapped to `None` are silent. Empty maps keys to consecutive
+    /// degrees.
+    pub degrees: Vec<Option<i32>>,
+}
+
+impl KeyboardMapping {
+    /// Maps keys to consecutive degrees with key 60 as the root and key 69 at
+    /// 440Hz, which is what Scala uses when no mapping is given.
+    pub fn linear(scale: &ScalaScale) -> Self {
+        Self {
+            first_key: 0,
+            last_key: 127,
+            middle_key: 60,
+            reference_key: 69,
+            reference_hertz: 440.,
+            octave_degree: scale.ratios.len() as i32,
+            degrees: Vec::new(),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut lines = data_lines(source);
+        let size = parse_next::<usize, _>(&mut lines, "map size")?;
+        let first_key = parse_next(&mut lines, "first key")?;
+        let last_key = parse_next(&mut lines, "last key")?;
+        let middle_key = parse_next(&mut lines, "middle key")?;
+        let reference_key = parse_next(&mut lines, "reference key")?;
+        let reference_hertz = parse_next(&mut lines, "reference frequency")?;
+        let octave_degree = parse_next(&mut lines, "octave degree")?;
+        let degrees = lines
+            .filter(|(_, text)| !text.is_empty())
+            .take(size)
+            .map(|(line, text)| match text {
+                "x" => Ok(None),
+                degree => degree
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| parse_error(line, "invalid scale degree")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if degrees.len() != size {
+            return Err(parse_error(0, "mapping has fewer keys than its map size"));
+        }
+
+        Ok(Self {
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_hertz,
+            octave_degree,
+            degrees,
+        })
+    }
+
+    fn degree(&self, key: u8) -> Option<i32> {
+        if key < self.first_key || key > self.last_key {
+            return None;
+        }
+
+        let offset = key as i32 - self.middle_key as i32;
+        if self.degrees.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.degrees.len() as i32;
+        let degree = self.degrees[offset.rem_euclid(size) as usize]?;
+        Some(degree + offset.div_euclid(size) * self.octave_degree)
+    }
+}
+
+/// Tunes keys using a Scala scale and keyboard mapping.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaTuning {
+    pub scale: ScalaScale,
+    pub mapping: KeyboardMapping,
+}
+
+impl ScalaTuning {
+    pub fn new(scale: ScalaScale, mapping: KeyboardMapping) -> Self {
+        Self { scale, mapping }
+    }
+
+    pub fn linear(scale: ScalaScale) -> Self {
+        let mapping = KeyboardMapping::linear(&scale);
+        Self::new(scale, mapping)
+    }
+}
+
+impl Tuning for ScalaTuning {
+    fn hertz(&self, key: u8) -> Option<f32> {
+        let degree = self.mapping.degree(key)?;
+        // The reference key sets the frequency even when it isn't mapped, so
+        // its degree is based on its position relative to the middle key.
+        let reference_degree = self
+            .mapping
+            .degree(self.mapping.reference_key)
+            .unwrap_or(self.mapping.reference_key as i32 - self.mapping.middle_key as i32);
+        let ratio = self.scale.ratio(degree)? / self.scale.ratio(reference_degree)?;
+        Some((self.mapping.reference_hertz * ratio) as f32)
+    }
+}
+
+/// The lines that aren't comments, along with their line numbers. Blank lines
+/// are kept because a scale's description may be empty.
+fn data_lines(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+fn parse_next<'a, T: std::str::FromStr, I: Iterator<Item = (usize, &'a str)>>(
+    lines: &mut I,
+    name: &str,
+) -> Result<T, Error> {
+    let (line, text) = next_line(lines, name)?;
+    parse_word(line, text, name)
+}
+
+fn next_line<'a, I: Iterator<Item = (usize, &'a str)>>(
+    lines: &mut I,
+    name: &str,
+) -> Result<(usize, &'a str), Error> {
+    lines
+        .find(|(_, text)| !text.is_empty())
+        .ok_or_else(|| parse_error(0, &format!("missing {}", name)))
+}
+
+fn parse_word<T: std::str::FromStr>(line: usize, text: &str, name: &str) -> Result<T, Error> {
+    first_word(text)
+        .parse()
+        .map_err(|_| parse_error(line, &format!("invalid {}", name)))
+}
+
+/// Pitches are cents if they contain a period, and ratios otherwise. Anything
+/// after the first word is a comment.
+fn parse_pitch(text: &str) -> Option<f64> {
+    let pitch = first_word(text);
+    if pitch.contains('.') {
+        let cents = pitch.parse::<f64>().ok()?;
+        Some(2f64.powf(cents / 1200.))
+    } else {
+        let (numerator, denominator) = match pitch.split_once('/') {
+            Some((numerator, denominator)) => (numerator, denominator),
+            None => (pitch, "1"),
+        };
+        let ratio = numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?;
+        if ratio > 0. {
+            Some(ratio)
+        } else {
+            None
+        }
+    }
+}
+
+fn first_word(text: &str) -> &str {
+    text.split_whitespace().next().unwrap_or_default()
+}
+
+fn parse_error(line: usize, message: &str) -> Error {
+    Error::Parse {
+        line,
+        message: message.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Option<f32>, b: f32) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 0.01)
+    }
+
+    #[test]
+    fn equal_divisions() {
+        let standard = EqualDivision::default();
+        assert!(close(standard.hertz(69), 440.));
+        assert!(close(standard.hertz(81), 880.));
+        assert!(close(standard.hertz(60), 261.63));
+        assert!(close(
+            EqualDivision::default().with_reference(69, 432.).hertz(57),
+            216.
+        ));
+
+        let nineteen = EqualDivision::new(19);
+        assert!(close(nineteen.hertz(69 + 19), 880.));
+        assert!(close(nineteen.hertz(70), 440. * 2f32.powf(1. / 19.)));
+        assert_eq!(EqualDivision::new(0).hertz(69), None);
+    }
+
+    #[test]
+    fn just_intonation() {
+        let just = JustIntonation::five_limit(60, 264.);
+        assert!(close(just.hertz(60), 264.));
+        assert!(close(just.hertz(67), 396.));
+        assert!(close(just.hertz(64 - 12), 165.));
+        assert!(close(just.hertz(72), 528.));
+        assert_eq!(JustIntonation::new(Vec::new(), 60, 264.).hertz(60), None);
+    }
+
+    #[test]
+    fn scala_files() {
+        let scale = ScalaScale::parse(
+            "! pentatonic.scl\n\
+             !\n\
+             Just pentatonic\n 5\n!\n 9/8\n 5/4 major third\n 701.955\n 5/3\n 2\n",
+        )
+        .unwrap();
+        assert_eq!(scale.description, "Just pentatonic");
+        assert_eq!(scale.ratios.len(), 5);
+
+        let linear = ScalaTuning::linear(scale.clone());
+        // Key 69 is degree 9, or an octave above degree 4
+        assert!(close(linear.hertz(69), 440.));
+        assert!(close(linear.hertz(65), 440. / 5. * 3.));
+        assert!(close(linear.hertz(70), 440. / 5. * 3. * 2.));
+        assert!(close(linear.hertz(71), 440. / 5. * 3. * 2. * 9. / 8.));
+
+        let mapping = KeyboardMapping::parse(
+            "! white keys only\n\
+             7\n 0\n 127\n 60\n 69\n 432.0\n 5\n\
+             0\n 1\n 2\n x\n 3\n 4\n x\n",
+        )
+        .unwrap();
+        let mapped = ScalaTuning::new(scale, mapping);
+        assert!(close(mapped.hertz(69), 432.));
+        assert!(close(mapped.hertz(60), 432. * 2. / 5.));
+        assert!(close(mapped.hertz(64), 432. * 2. / 5. * 3. / 2.));
+        assert_eq!(mapped.hertz(63), None);
+        assert!(close(mapped.hertz(67), 432. * 2. / 5. * 2.));
+
+        let spaced = ScalaScale::parse("Spaced\n3\n\n6/5\n\n3/2\n\n2\n").unwrap();
+        assert_eq!(spaced.ratios, vec![6. / 5., 3. / 2., 2.]);
+
+        assert!(matches!(
+            ScalaScale::parse("Broken\n2\n3/2\n"),
+            Err(Error::Parse { .. })
+        ));
+        assert!(matches!(
+            ScalaScale::parse("Empty\n0\n"),
+            Err(Error::Parse { line: 2, .. })
+        ));
+
+        let empty = ScalaScale {
+            description: String::new(),
+            ratios: Vec::new(),
+        };
+        assert_eq!(empty.ratio(0), None);
+        assert_eq!(ScalaTuning::linear(empty).hertz(60), None);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::sampler::Sample;
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, Write},
+    path::Path,
+};
+
+pub use hound::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    Int16,
+    Float32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Mono,
+    Stereo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavSpec {
+    pub sample_rate: u32,
+    pub channels: Channels,
+    pub format: WavFormat,
+}
+
+impl WavSpec {
+    pub fn new(sample_rate: u32, channels: Channels, format: WavFormat) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            format,
+        }
+    }
+}
+
+impl From<WavSpec> for hound::WavSpec {
+    fn from(spec: WavSpec) -> Self {
+        let (bits_per_sample, sample_format) = match spec.format {
+            WavFormat::Int16 => (16, hound::SampleFormat::Int),
+            WavFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+
+        hound::WavSpec {
+            channels: match spec.channels {
+                Channels::Mono => 1,
+                Channels::Stereo => 2,
+            },
+            sample_rate: spec.sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+pub struct WavWriter<W>
+where
+    W: Write + Seek,
+{
+    writer: hound::WavWriter<W>,
+    spec: WavSpec,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, spec: WavSpec) -> Result<Self, Error> {
+        Ok(Self {
+            writer: hound::WavWriter::create(path, spec.into())?,
+            spec,
+        })
+    }
+}
+
+impl<W> WavWriter<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(writer: W, spec: WavSpec) -> Result<Self, Error> {
+        Ok(Self {
+            writer: hound::WavWriter::new(writer, spec.into())?,
+            spec,
+        })
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    pub fn write_sample(&mut self, sample: Sample) -> Result<(), Error> {
+        let sample = sample.clamped();
+        match self.spec.channels {
+            Channels::Mono => self.write_value((sample.left + sample.right) / 2.),
+            Channels::Stereo => {
+                self.write_value(sample.left)?;
+                self.write_value(sample.right)
+            }
+        }
+    }
+
+    pub fn write_samples(&mut self, samples: &[Sample]) -> Result<(), Error> {
+        for sample in samples {
+            self.write_sample(*sample)?;
+        }
+        Ok(())
+    }
+
+    fn write_value(&mut self, value: f32) -> Result<(), Error> {
+        match self.spec.format {
+            WavFormat::Int16 => self
+                .writer
+                .write_sample((value * i16::MAX as f32).round() as i16),
+            WavFormat::Float32 => self.writer.write_sample(value),
+        }
+    }
+
+    /// Updates the header so that everything written so far is a valid file,
+    /// even if the writer is never finalized.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    pub fn finalize(self) -> Result<(), Error> {
+        self.writer.finalize()
+    }
+}
+
+pub fn write<P: AsRef<Path>>(path: P, spec: WavSpec, samples: &[Sample]) -> Result<(), Error> {
+    let mut writer = WavWriter::create(path, spec)?;
+    writer.write_samples(samples)?;
+    writer.finalize()
+}
+
+/// Audio decoded from a WAV file.
+#[derive(Debug, Clone)]
+pub struct WavData {
+    pub sample_rate: u32,
+    /// One sample per frame. Mono files play the same value on both sides,
+    /// and files with more than two channels only keep the first two.
+    pub samples: Vec<Sample>,
+}
+
+impl WavData {
+    /// The average of both sides of each sample.
+    pub fn mono(&self) -> Vec<f32> {
+        self.samples
+            .iter()
+            .map(|sample| (sample.left + sample.right) / 2.)
+            .collect()
+    }
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> Result<WavData, Error> {
+    read_from(hound::WavReader::open(path)?)
+}
+
+pub fn read_from<R: Read>(reader: hound::WavReader<R>) -> Result<WavData, Error> {
+    let spec = reader.spec();
+    let values = match spec.sample_format {
+        hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 2f32.powi(spec.bits_per_sample as i32 - 1);
+            reader
+                .into_samples::<i32>()
+                .map(|value| value.map(|value| value as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let samples = values
+        .chunks(channels)
+        .map(|frame| Sample {
+            left: frame[0],
+            right: *frame.get(1).unwrap_or(&frame[0]),
+        })
+        .collect();
+
+    Ok(WavData {
+        sample_rate: spec.sample_rate,
+        samples,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn round_trip(spec: WavSpec, samples: &[Sample]) -> hound::WavReader<Cursor<Vec<u8>>> {
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut buffer, spec).unwrap();
+        writer.write_samples(samples).unwrap();
+        writer.finalize().unwrap();
+        buffer.set_position(0);
+        hound::WavReader::new(buffer).unwrap()
+    }
+
+    #[test]
+    fn int16_mono() {
+        let samples = [
+            Sample {
+                left: 1.,
+                right: 0.,
+            },
+            Sample {
+                left: -2.,
+                right: -2.,
+            },
+        ];
+        let mut reader = round_trip(
+            WavSpec::new(8_000, Channels::Mono, WavFormat::Int16),
+            &samples,
+        );
+        assert_eq!(reader.spec().channels, 1);
+        let values = reader
+            .samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, vec![i16::MAX / 2 + 1, -i16::MAX]);
+    }
+
+    #[test]
+    fn float32_stereo() {
+        let samples = [Sample {
+            left: 0.25,
+            right: -0.5,
+        }];
+        let mut reader = round_trip(
+            WavSpec::new(48_000, Channels::Stereo, WavFormat::Float32),
+            &samples,
+        );
+        assert_eq!(reader.spec().sample_rate, 48_000);
+        let values = reader
+            .samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, vec![0.25, -0.5]);
+    }
+
+    #[test]
+    fn read_int16_mono() {
+        let samples = [Sample {
+            left: 0.5,
+            right: 0.5,
+        }];
+        let data = read_from(round_trip(
+            WavSpec::new(8_000, Channels::Mono, WavFormat::Int16),
+            &samples,
+        ))
+        .unwrap();
+        assert_eq!(data.sample_rate, 8_000);
+        assert_eq!(data.samples.len(), 1);
+        assert!((data.samples[0].right - 0.5).abs() < 0.001);
+    }
+}